@@ -1,9 +1,12 @@
 #![no_std]
-#![feature(allocator_api)]
+#![cfg_attr(all(test, feature = "std"), feature(allocator_api))]
 
 #[cfg(feature = "runtime")]
 pub use dependability_runtime as runtime;
 
+#[cfg(feature = "runtime")]
+pub use dependability_runtime::proc_macro::{main, task};
+
 #[cfg(feature = "retry")]
 pub use dependability_retry as retry;
 
@@ -22,6 +25,7 @@ mod tests {
         time::{StdTimer, Timer},
     };
     use std::alloc::Global;
+    use std::sync::atomic::{AtomicU64, Ordering};
 
     async fn complex_task(id: usize) {
         println!("Task {} here!", id);
@@ -54,4 +58,24 @@ mod tests {
         //assert!(executor.run().is_err());
         assert!(executor.run().is_ok());
     }
+
+    #[crate::task(crate = crate::runtime, deadline = 10, wcet = 5, name = "counter")]
+    async fn count(counter: &'static AtomicU64, by: u64) {
+        noop().await;
+        counter.fetch_add(by, Ordering::Relaxed);
+    }
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    #[crate::main(crate = crate::runtime, timer = StdTimer)]
+    fn run_counters() -> [Task; 2] {
+        [count(&COUNTER, 1), count(&COUNTER, 2)]
+    }
+
+    #[test]
+    fn test_task_and_main_attributes() {
+        assert_eq!(count(&COUNTER, 0).name(), Some("counter"));
+        assert!(run_counters().is_ok());
+        assert_eq!(COUNTER.load(Ordering::Relaxed), 3);
+    }
 }
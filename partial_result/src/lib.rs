@@ -1,5 +1,4 @@
 #![no_std]
-#![feature(async_closure)]
 
 extern crate alloc;
 
@@ -89,7 +88,7 @@ mod tests {
         let mut executor = Executor::new();
         let now = StdTimer.now();
 
-        let output = executor.spawn_partial::<i32, _>(now.into(), async move |slot| {
+        let output = executor.spawn_partial::<i32, _>(now.into(), |slot| async move {
             slot.set(39);
             noop().await;
             slot.set(44);
@@ -1,7 +1,7 @@
 use dependability_retry_proc_macro::retry;
 
 // Our connection isn't great, so connect() will always fail
-fn connect(_host: &'static str, _port: u16) -> Option<&[u8]> {
+fn connect(_host: &'static str, _port: u16) -> Option<&'static [u8]> {
     None
 }
 
@@ -7,8 +7,8 @@ fn replace_return_type(r: &ReturnType) -> Result<ReturnType, &'static str> {
     if let syn::ReturnType::Type(_, t) = r {
         if let syn::Type::Path(p) = t.as_ref() {
             let p = &p.path;
-            let pairs = p.segments.pairs();
-            let last = &pairs.last().unwrap();
+            let mut pairs = p.segments.pairs();
+            let last = &pairs.next_back().unwrap();
             let value = &last.value();
             let arguments = &value.arguments;
             if let PathArguments::AngleBracketed(generics) = arguments {
@@ -28,8 +28,8 @@ fn returns_type(r: &ReturnType, ty: &str) -> bool {
     if let syn::ReturnType::Type(_, t) = r {
         if let syn::Type::Path(p) = t.as_ref() {
             let p = &p.path;
-            let pairs = p.segments.pairs();
-            let last = &pairs.last().unwrap();
+            let mut pairs = p.segments.pairs();
+            let last = &pairs.next_back().unwrap();
             let value = &last.value();
             return value.ident == ty;
         }
@@ -1,6 +1,13 @@
+//! Without `std`, the runtime keeps the storage of the polled task and the
+//! installed safe state in globals instead of thread-locals. They're only
+//! loaded and stored, never read-modified-written atomically, so this assumes
+//! a single execution context: Interrupt handlers must neither poll tasks nor
+//! use task-locals, and a safe state has to be installed before an interrupt
+//! handler may enter it.
+
 #![no_std]
 
-use alloc::sync::Arc;
+use alloc::rc::Rc;
 use core::cell::Cell;
 
 extern crate alloc;
@@ -9,16 +16,71 @@ pub mod priority_queue;
 pub mod task;
 pub mod time;
 
+pub mod proc_macro {
+    pub use dependability_runtime_proc_macro::{main, task};
+}
+
 #[cfg(feature = "std")]
 extern crate std;
 
-#[cfg(feature = "std")]
+/// A slot a task stores its latest partial result in, so it can still be used
+/// after the task was aborted.
+pub struct PartialSink<T> {
+    value: Cell<Option<T>>,
+}
+
+impl<T: Copy> PartialSink<T> {
+    pub fn new() -> Rc<Self> {
+        Rc::new(Self {
+            value: Cell::new(None),
+        })
+    }
+
+    pub fn set(&self, value: T) {
+        self.value.set(Some(value));
+    }
+
+    pub fn get(&self) -> Option<T> {
+        self.value.get()
+    }
+}
+
+/// Runs the given futures on a new executor and returns a tuple with the
+/// outcome of each of them.
+///
+/// Each future is given as `(deadline, future)` or
+/// `(deadline, future, strategy)`. Tasks without a strategy return an error
+/// when they miss their deadline. Failing tasks don't stop the other tasks.
+///
+/// By default, the executor uses the [`StdTimer`](time::StdTimer), which is
+/// only available with the `std` feature. Other timers can be given before
+/// the tasks: `spawn!(timer; (deadline, future), ...)`.
 #[macro_export]
 macro_rules! spawn {
-    ($(($deadline:expr, $task:expr)),+) => {{
-        let mut executor = crate::task::executor::Executor::new();
-        $(executor.spawn(Task::new($deadline, crate::task::DelayStrategy::ReturnError, $task)));+;
-        executor.run()
+    (@strategy) => {
+        $crate::task::DelayStrategy::ReturnError
+    };
+    (@strategy $strategy:expr) => {
+        $strategy
+    };
+    ($(($deadline:expr, $future:expr $(, $strategy:expr)?)),+ $(,)?) => {
+        $crate::spawn!($crate::time::StdTimer; $(($deadline, $future $(, $strategy)?)),+)
+    };
+    ($timer:expr; $(($deadline:expr, $future:expr $(, $strategy:expr)?)),+ $(,)?) => {{
+        let mut executor = $crate::task::executor::Executor::with_timer($timer);
+        let handles = ($({
+            let (task, handle) = $crate::task::Task::joinable(
+                $deadline,
+                $crate::spawn!(@strategy $($strategy)?),
+                $future,
+            );
+            executor.spawn(task);
+            handle
+        },)+);
+        // Errors are reported through the handles. The executor drops failed
+        // tasks, so running it again continues with the remaining ones.
+        while executor.run().is_err() {}
+        $crate::task::join::JoinAll::join_all(handles)
     }};
 }
 
@@ -26,11 +88,50 @@ macro_rules! spawn {
 mod tests {
     extern crate std;
     use crate::{
-        task::{executor::Executor, noop, sleep, DelayStrategy, Task},
-        time::{StdTimer, Timer},
+        task::{
+            approximate::Approximation,
+            console::Console,
+            criticality::{Criticality, CriticalityMode, ExecutionBudget, LowCriticalityPolicy},
+            cyclic::{CyclicExecutive, FrameOverrun, ScheduleError, ScheduleTable},
+            deadline::Deadline,
+            executor::{Executor, ExecutorError, TaskState},
+            firm::{Escalation, MkFirm},
+            graph::TaskGraph,
+            local, noop,
+            partition::{PartitionedExecutor, WindowOverrun},
+            ports::Port,
+            restart::Restart,
+            safe_state::{SafeState, SafeStateError, SafeStateRegistry},
+            scope::{scope, ScopeError, ScopeMode},
+            sleep,
+            spawner::SpawnPolicy,
+            supervision::{self, SupervisedEntity, Violation},
+            supervisor::{Escalated, RestartStrategy, Supervisor},
+            watchdog::{self, TestWatchdog, WatchdogConfig},
+            DelayStrategy, Task,
+        },
+        time::{StdTimer, Timer, Timestamp},
+        PartialSink,
     };
+    use alloc::{boxed::Box, rc::Rc, vec::Vec};
+    use core::cell::Cell;
     use std::println;
 
+    /// A timer that only advances when tasks `delay` on it, so tests can
+    /// simulate long computations without actually waiting.
+    #[derive(Clone, Default)]
+    struct TestTimer(Rc<Cell<Timestamp>>);
+
+    impl Timer for TestTimer {
+        fn now(&self) -> Timestamp {
+            self.0.get()
+        }
+
+        fn delay(&self, duration: core::time::Duration) {
+            self.0.set(self.0.get() + duration.as_secs());
+        }
+    }
+
     async fn async_number() -> u32 {
         42
     }
@@ -89,11 +190,26 @@ mod tests {
     #[test]
     fn test_macro() {
         let now = StdTimer.now();
-        assert!(spawn!(
+        let (first, second) = spawn!(
             ((now + 4).into(), async_task(1)),
             ((now + 2).into(), async_task(2))
-        )
-        .is_ok());
+        );
+        assert!(first.is_ok() && second.is_ok());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_macro_with_timer_and_strategies() {
+        let timer = TestTimer::default();
+        let (answer, late, aborted) = spawn!(
+            timer.clone();
+            (10.into(), async { 42 }),
+            (2.into(), compute(timer.clone(), 5)),
+            (3.into(), compute(timer.clone(), 6), DelayStrategy::SilentlyAbort),
+        );
+        assert_eq!(answer, Ok(42));
+        assert!(matches!(late, Err(ExecutorError::MissedDeadline(_))));
+        assert!(matches!(aborted, Err(ExecutorError::Cancelled(_))));
     }
 
     async fn pending_task(number: u8) {
@@ -105,7 +221,8 @@ mod tests {
     #[cfg(feature = "std")]
     #[test]
     fn test_missing_deadline() {
-        assert!(spawn!(((StdTimer.now() + 2).into(), pending_task(1))).is_err());
+        let (result,) = spawn!(((StdTimer.now() + 2).into(), pending_task(1)));
+        assert!(matches!(result, Err(ExecutorError::MissedDeadline(_))));
     }
 
     async fn long_task(mut seconds: u8) {
@@ -136,7 +253,7 @@ mod tests {
         assert!(executor.run().is_ok());
     }
 
-    async fn partial(sink: alloc::sync::Arc<PartialSink<u8>>) {
+    async fn partial(sink: Rc<PartialSink<u8>>) {
         let v1 = 9;
         sink.set(v1);
         std::thread::sleep(core::time::Duration::from_secs(10));
@@ -183,12 +300,14 @@ mod tests {
             DelayStrategy::ReturnError,
             complex_task(0),
         ));
-        executor.spawn(Task::new(
+        let urgent = executor.spawn(Task::new(
             (now + 4).into(),
             DelayStrategy::ReturnError,
             complex_task(1),
         ));
-        executor.run().unwrap();
+        // Each subtask takes a second and then never finishes, so the more
+        // urgent task misses its deadline first.
+        assert_eq!(executor.run(), Err(ExecutorError::MissedDeadline(urgent)));
     }
 
     #[cfg(feature = "std")]
@@ -215,4 +334,1513 @@ mod tests {
 
         assert!(exec.run().is_ok())
     }
+
+    async fn compute(timer: TestTimer, seconds: u64) {
+        timer.delay(core::time::Duration::from_secs(seconds));
+        noop::noop().await;
+    }
+
+    async fn telemetry(sent: Rc<Cell<bool>>) {
+        noop::noop().await;
+        sent.set(true);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mixed_criticality() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let sent = Rc::new(Cell::new(false));
+
+        executor.spawn(
+            Task::new(
+                5.into(),
+                DelayStrategy::ReturnError,
+                compute(timer.clone(), 2),
+            )
+            .with_criticality(Criticality::High)
+            .with_budget(ExecutionBudget::new(1, 3)),
+        );
+        executor.spawn(Task::new(
+            10.into(),
+            DelayStrategy::ReturnError,
+            telemetry(sent.clone()),
+        ));
+
+        assert!(executor.run().is_ok());
+        assert!(!sent.get());
+        assert_eq!(executor.mode(), CriticalityMode::Low);
+
+        let mut executor = Executor::with_timer(timer.clone());
+        executor.set_low_criticality_policy(LowCriticalityPolicy::Degrade);
+        executor.spawn(
+            Task::new(
+                (timer.now() + 5).into(),
+                DelayStrategy::ReturnError,
+                compute(timer.clone(), 2),
+            )
+            .with_criticality(Criticality::High)
+            .with_budget(ExecutionBudget::new(1, 3)),
+        );
+        executor.spawn(Task::new(
+            (timer.now() + 10).into(),
+            DelayStrategy::ReturnError,
+            telemetry(sent.clone()),
+        ));
+        assert!(executor.run().is_ok());
+        assert!(sent.get());
+
+        // Dropped tasks are cancelled, so their pending releases don't keep
+        // the executor waiting.
+        let mut executor = Executor::with_timer(timer.clone());
+        let start = timer.now();
+        executor.spawn(Task::periodic(100, 1, DelayStrategy::ReturnError, || {
+            noop::noop()
+        }));
+        executor.spawn(
+            Task::new(
+                (start + 5).into(),
+                DelayStrategy::ReturnError,
+                compute(timer.clone(), 2),
+            )
+            .with_criticality(Criticality::High)
+            .with_budget(ExecutionBudget::new(1, 3)),
+        );
+        assert!(executor.run().is_ok());
+        assert_eq!(timer.now(), start + 2);
+        assert_eq!(executor.stats().failed, 1);
+
+        // The executor is idle once the last high-criticality task failed.
+        let mut executor = Executor::with_timer(timer.clone());
+        let start = timer.now();
+        let late = executor.spawn(
+            Task::new(
+                (start + 2).into(),
+                DelayStrategy::ReturnError,
+                compute(timer.clone(), 3),
+            )
+            .with_criticality(Criticality::High)
+            .with_budget(ExecutionBudget::new(1, 5)),
+        );
+        assert_eq!(executor.run(), Err(ExecutorError::MissedDeadline(late)));
+        assert_eq!(executor.mode(), CriticalityMode::Low);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_budget_overrun() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        executor.spawn(
+            Task::new(
+                10.into(),
+                DelayStrategy::ReturnError,
+                compute(timer.clone(), 4),
+            )
+            .with_criticality(Criticality::High)
+            .with_budget(ExecutionBudget::new(1, 3)),
+        );
+        assert!(matches!(
+            executor.run(),
+            Err(ExecutorError::BudgetOverrun(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_periodic_task() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let jobs = Rc::new(Cell::new(0));
+
+        let jobs_clone = jobs.clone();
+        let timer_clone = timer.clone();
+        executor.spawn(
+            Task::periodic(10, 5, DelayStrategy::ReturnError, move || {
+                jobs_clone.set(jobs_clone.get() + 1);
+                compute(timer_clone.clone(), 1)
+            })
+            .with_job_limit(3),
+        );
+
+        assert!(executor.run().is_ok());
+        assert_eq!(jobs.get(), 3);
+        assert!(timer.now() >= 20);
+    }
+
+    fn firm_task(timer: &TestTimer, durations: &'static [u64], firm: MkFirm) -> Task {
+        let timer = timer.clone();
+        let next = Rc::new(Cell::new(0));
+        Task::periodic(10, 5, DelayStrategy::MkFirm(firm), move || {
+            next.set(next.get() + 1);
+            compute(timer.clone(), durations[next.get() - 1])
+        })
+        .with_job_limit(durations.len() as u64)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mk_firm_deadlines() {
+        let timer = TestTimer::default();
+        let durations = &[6, 1, 6];
+
+        let mut executor = Executor::with_timer(timer.clone());
+        executor.spawn(firm_task(
+            &timer,
+            durations,
+            MkFirm::new(1, 3, Escalation::ReturnError),
+        ));
+        assert!(executor.run().is_ok());
+
+        let mut executor = Executor::with_timer(timer.clone());
+        executor.spawn(firm_task(
+            &timer,
+            durations,
+            MkFirm::new(2, 3, Escalation::ReturnError),
+        ));
+        assert!(matches!(
+            executor.run(),
+            Err(ExecutorError::ConstraintViolated(_))
+        ));
+
+        let mut executor = Executor::with_timer(timer.clone());
+        let violations = Rc::new(Cell::new(0));
+        let violations_clone = violations.clone();
+        let escalation = Escalation::Callback(alloc::boxed::Box::new(move |_| {
+            violations_clone.set(violations_clone.get() + 1)
+        }));
+        executor.spawn(firm_task(&timer, durations, MkFirm::new(2, 3, escalation)));
+        assert!(executor.run().is_ok());
+        assert_eq!(violations.get(), 1);
+
+        // A job that finishes late within a single poll missed its deadline.
+        let mut executor = Executor::with_timer(timer.clone());
+        let timer_clone = timer.clone();
+        executor.spawn(
+            Task::periodic(
+                10,
+                5,
+                DelayStrategy::MkFirm(MkFirm::new(1, 1, Escalation::ReturnError)),
+                move || {
+                    let timer = timer_clone.clone();
+                    async move { timer.delay(core::time::Duration::from_secs(6)) }
+                },
+            )
+            .with_job_limit(1),
+        );
+        assert!(matches!(
+            executor.run(),
+            Err(ExecutorError::ConstraintViolated(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mk_firm_skips_jobs() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let jobs = Rc::new(Cell::new(0));
+
+        let jobs_clone = jobs.clone();
+        executor.spawn(
+            Task::periodic(
+                10,
+                2,
+                DelayStrategy::MkFirm(MkFirm::new(1, 2, Escalation::ReturnError)),
+                move || {
+                    let jobs = jobs_clone.clone();
+                    async move { jobs.set(jobs.get() + 1) }
+                },
+            )
+            .with_budget(ExecutionBudget::new(3, 3))
+            .with_job_limit(4),
+        );
+
+        assert!(executor.run().is_ok());
+        assert_eq!(jobs.get(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_restart_failing_task() {
+        let attempts = Rc::new(core::cell::RefCell::new(alloc::vec::Vec::new()));
+        let attempts_clone = attempts.clone();
+        let flaky = move |limit| {
+            let attempts = attempts_clone.clone();
+            Task::restarting(
+                Deadline::Infinite,
+                Restart::new(10, limit, move |restarts| {
+                    attempts.borrow_mut().push(restarts);
+                    async move {
+                        if restarts < 2 {
+                            Err("not yet")
+                        } else {
+                            Ok(())
+                        }
+                    }
+                }),
+            )
+        };
+
+        let mut executor = Executor::with_timer(TestTimer::default());
+        executor.spawn(flaky(3));
+        assert!(executor.run().is_ok());
+        assert_eq!(*attempts.borrow(), [0, 1, 2]);
+
+        let mut executor = Executor::with_timer(TestTimer::default());
+        executor.spawn(flaky(1));
+        assert!(matches!(
+            executor.run(),
+            Err(ExecutorError::RestartsExhausted(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_restart_after_missed_deadline() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let timer_clone = timer.clone();
+        executor.spawn(Task::restarting(
+            5.into(),
+            Restart::new(5, 1, move |restarts| {
+                compute(timer_clone.clone(), if restarts == 0 { 6 } else { 1 })
+            })
+            .with_delay(2),
+        ));
+        assert!(executor.run().is_ok());
+        assert!(timer.now() >= 9);
+    }
+
+    async fn refine(timer: TestTimer, estimate: Rc<PartialSink<u8>>) {
+        for value in 1..=10 {
+            estimate.set(value);
+            timer.delay(core::time::Duration::from_secs(1));
+            noop::noop().await;
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_approximate_from_partial_result() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let estimate = PartialSink::new();
+        let result = PartialSink::new();
+
+        let result_clone = result.clone();
+        executor.spawn(Task::new(
+            3.into(),
+            DelayStrategy::InsteadApproximate(Approximation::new(
+                estimate.clone(),
+                2,
+                move |estimate| {
+                    let result = result_clone.clone();
+                    async move { result.set(estimate.unwrap_or(0) * 10) }
+                },
+            )),
+            refine(timer.clone(), estimate.clone()),
+        ));
+
+        assert!(executor.run().is_ok());
+        assert_eq!(result.get(), Some(30));
+        // Both the original future and the approximation are gone.
+        assert_eq!(Rc::strong_count(&estimate), 1);
+    }
+
+    crate::task_local! {
+        static REQUEST_ID: Cell<u32> = Cell::new(0);
+        static HANDLED: Cell<bool> = Cell::new(false);
+    }
+
+    async fn log_request_id(seen: Rc<core::cell::RefCell<alloc::vec::Vec<u32>>>) {
+        noop::noop().await;
+        seen.borrow_mut().push(REQUEST_ID.with(|id| id.get()));
+    }
+
+    async fn handle_request(id: u32, seen: Rc<core::cell::RefCell<alloc::vec::Vec<u32>>>) {
+        REQUEST_ID.with(|request_id| request_id.set(id));
+        log_request_id(seen).await;
+        HANDLED.with(|handled| {
+            // Accessing another variable while one is borrowed works.
+            handled.set(REQUEST_ID.with(|request_id| request_id.get()) == id)
+        });
+        assert!(HANDLED.with(|handled| handled.get()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_task_locals() {
+        let mut executor = Executor::with_timer(TestTimer::default());
+        let seen = Rc::new(core::cell::RefCell::new(alloc::vec::Vec::new()));
+
+        executor.spawn(Task::new(
+            5.into(),
+            DelayStrategy::ReturnError,
+            handle_request(1, seen.clone()),
+        ));
+        executor.spawn(Task::new(
+            4.into(),
+            DelayStrategy::ReturnError,
+            handle_request(2, seen.clone()),
+        ));
+        executor.spawn(Task::new(7.into(), DelayStrategy::ReturnError, async {
+            assert_eq!(local::deadline(), Some(Deadline::Finite(7)));
+        }));
+
+        assert!(executor.run().is_ok());
+        seen.borrow_mut().sort();
+        assert_eq!(*seen.borrow(), [1, 2]);
+        assert!(REQUEST_ID.try_with(|id| id.get()).is_err());
+        assert_eq!(local::deadline(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_step_wise_execution() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        assert_eq!(executor.next_wakeup(), None);
+
+        let jobs = Rc::new(Cell::new(0));
+        let jobs_clone = jobs.clone();
+        executor.spawn(
+            Task::periodic(10, 5, DelayStrategy::ReturnError, move || {
+                let jobs = jobs_clone.clone();
+                async move { jobs.set(jobs.get() + 1) }
+            })
+            .with_job_limit(5),
+        );
+        assert_eq!(executor.next_wakeup(), Some(0));
+
+        assert!(executor.poll_once().unwrap());
+        assert_eq!(jobs.get(), 1);
+        assert!(!executor.poll_once().unwrap());
+        assert_eq!(executor.next_wakeup(), Some(10));
+
+        executor.run_until(25).unwrap();
+        assert_eq!(jobs.get(), 3);
+        assert_eq!(timer.now(), 25);
+        assert_eq!(executor.next_wakeup(), Some(30));
+
+        executor
+            .run_for(core::time::Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(jobs.get(), 4);
+        assert_eq!(timer.now(), 35);
+
+        executor.run().unwrap();
+        assert_eq!(jobs.get(), 5);
+        assert_eq!(executor.next_wakeup(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_spawn_traits_and_block_on() {
+        use futures_util::task::LocalSpawnExt;
+
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        executor.set_spawn_policy(SpawnPolicy {
+            relative_deadline: Some(5),
+            strategy: || DelayStrategy::SilentlyAbort,
+        });
+        let spawner = executor.spawner();
+        let helped = Rc::new(Cell::new(false));
+
+        let helped_clone = helped.clone();
+        let answer = executor.block_on(
+            async move {
+                let helper = helped_clone.clone();
+                spawner
+                    .spawn_local(async move { helper.set(true) })
+                    .unwrap();
+                while !helped_clone.get() {
+                    noop::noop().await;
+                }
+                42
+            },
+            10.into(),
+        );
+        assert_eq!(answer.unwrap(), 42);
+        assert!(helped.get());
+
+        executor.spawn_local(compute(timer.clone(), 6)).unwrap();
+        assert!(executor.run().is_ok());
+
+        assert_eq!(
+            crate::task::executor::block_on(async { 42 }, Deadline::Infinite).unwrap(),
+            42
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_deadline_inheritance() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let seen = Rc::new(Cell::new(None));
+
+        // Without inheritance, the other task would run first and the
+        // consumer would miss its deadline.
+        let seen_clone = seen.clone();
+        let (producer, handle) = Task::joinable(100.into(), DelayStrategy::ReturnError, {
+            let timer = timer.clone();
+            async move {
+                compute(timer, 1).await;
+                seen_clone.set(local::deadline());
+                42
+            }
+        });
+        let consumer = Task::new(5.into(), DelayStrategy::ReturnError, async move {
+            assert_eq!(handle.await, Ok(42));
+        });
+        executor.spawn(consumer);
+        executor.spawn(producer);
+        executor.spawn(Task::new(
+            50.into(),
+            DelayStrategy::ReturnError,
+            compute(timer.clone(), 20),
+        ));
+        assert!(executor.run().is_ok());
+        assert_eq!(seen.get(), Some(Deadline::Finite(5)));
+        assert_eq!(timer.now(), 21);
+        // The producer is queued once, even though it was moved forward.
+        assert_eq!(executor.stats().polls, 7);
+    }
+
+    /// sensor -> filter -> controller and sensor -> logger -> controller, where
+    /// the filter takes the given time.
+    fn control_graph(
+        timer: &TestTimer,
+        filter_time: u64,
+    ) -> (TaskGraph, [Rc<Cell<Option<Deadline>>>; 2]) {
+        let mut graph = TaskGraph::new(10);
+        let sensor = graph.node("sensor", 1, (), {
+            let timer = timer.clone();
+            |()| async move {
+                compute(timer, 1).await;
+                21
+            }
+        });
+        let filter_deadline = Rc::new(Cell::new(None));
+        let filter = graph.node("filter", 2, sensor.clone(), {
+            let timer = timer.clone();
+            let deadline = filter_deadline.clone();
+            move |value: u64| async move {
+                deadline.set(local::deadline());
+                compute(timer, filter_time).await;
+                value * 2
+            }
+        });
+        let logger = graph.node("logger", 1, sensor, |_| async {});
+        let controller_deadline = Rc::new(Cell::new(None));
+        graph.node("controller", 1, (filter, logger), {
+            let deadline = controller_deadline.clone();
+            move |(value, ())| async move {
+                deadline.set(local::deadline());
+                assert_eq!(value, 42);
+            }
+        });
+        (graph, [filter_deadline, controller_deadline])
+    }
+
+    async fn record(order: Rc<core::cell::RefCell<Vec<&'static str>>>, name: &'static str) {
+        order.borrow_mut().push(name);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_absolute_deadline_priority() {
+        let timer = TestTimer::default();
+        timer.delay(core::time::Duration::from_secs(100));
+        let mut executor = Executor::with_timer(timer.clone());
+        let order = Rc::new(core::cell::RefCell::new(Vec::new()));
+        executor.spawn(
+            Task::new(
+                Deadline::Infinite,
+                DelayStrategy::ContinueRunning,
+                record(order.clone(), "relative"),
+            )
+            .with_relative_deadline(10),
+        );
+        // Due at 105, before the other task's 110.
+        executor.spawn(Task::new(
+            105.into(),
+            DelayStrategy::ContinueRunning,
+            record(order.clone(), "absolute"),
+        ));
+        assert!(executor.run().is_ok());
+        assert_eq!(*order.borrow(), ["absolute", "relative"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_task_graph() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let (graph, [filter_deadline, controller_deadline]) = control_graph(&timer, 2);
+        let handle = executor.spawn_graph(graph);
+        assert!(executor.run().is_ok());
+        assert!(handle.is_finished());
+        // The controller needs 1 second after the filter.
+        assert_eq!(filter_deadline.get(), Some(Deadline::Finite(9)));
+        assert_eq!(controller_deadline.get(), Some(Deadline::Finite(10)));
+
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let (graph, _) = control_graph(&timer, 9);
+        let handle = executor.spawn_graph(graph);
+        assert!(executor.run().is_err());
+        assert!(!handle.is_finished());
+        assert!(matches!(
+            handle.failed_stage(),
+            Some(("filter", ExecutorError::MissedDeadline(_)))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_task_graph_is_dropped_with_executor() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let captured = Rc::new(());
+        let mut graph = TaskGraph::new(10);
+        let first = graph.node("first", 1, (), |()| async {});
+        graph.node("second", 1, first, {
+            let captured = captured.clone();
+            move |()| async move { drop(captured) }
+        });
+        let handle = executor.spawn_graph(graph);
+        // Releases the second stage, which waits in the spawner.
+        assert!(executor.poll_once().is_ok());
+        drop(executor);
+        drop(handle);
+        assert_eq!(Rc::strong_count(&captured), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_constant_bandwidth_server() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let server = executor.add_server(2, 4);
+
+        // Aperiodic work with an earlier server deadline than the critical
+        // task that would make it miss its deadline if it weren't throttled.
+        let finished = Rc::new(Cell::new(None));
+        let finished_clone = finished.clone();
+        let aperiodic_timer = timer.clone();
+        executor.spawn(
+            Task::new(Deadline::Infinite, DelayStrategy::ReturnError, async move {
+                for _ in 0..6 {
+                    compute(aperiodic_timer.clone(), 1).await;
+                }
+                finished_clone.set(Some(aperiodic_timer.now()));
+            })
+            .in_server(server),
+        );
+        let critical_timer = timer.clone();
+        executor.spawn(Task::new(
+            7.into(),
+            DelayStrategy::ReturnError,
+            async move {
+                for _ in 0..3 {
+                    compute(critical_timer.clone(), 1).await;
+                }
+            },
+        ));
+
+        assert!(executor.run().is_ok());
+        // The aperiodic work only gets 2 out of every 4 seconds, so it's
+        // throttled after each 2 seconds of work until the next period.
+        assert_eq!(finished.get(), Some(12));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_server_out_of_range() {
+        let timer = TestTimer::default();
+        let server = Executor::with_timer(timer.clone()).add_server(2, 4);
+        let mut executor = Executor::with_timer(timer);
+        let task = Task::new(Deadline::Infinite, DelayStrategy::ReturnError, async {});
+        assert!(matches!(
+            executor.try_spawn(task.in_server(server)),
+            Err(ExecutorError::ServerOutOfRange(_))
+        ));
+        assert_eq!(executor.stats().tasks, 0);
+        assert!(executor.run().is_ok());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_logical_execution_time() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let sensor = Port::new(1);
+        let actuator = Port::new(0);
+
+        let input = sensor.input();
+        let output = actuator.output();
+        let job_timer = timer.clone();
+        executor.spawn(
+            Task::periodic(10, 10, DelayStrategy::ReturnError, {
+                let input = input.clone();
+                let output = output.clone();
+                move || {
+                    let (input, output, timer) = (input.clone(), output.clone(), job_timer.clone());
+                    async move {
+                        compute(timer, 3).await;
+                        output.set(input.get() * 2);
+                    }
+                }
+            })
+            .with_job_limit(2)
+            .with_input(&input)
+            .with_output(&output),
+        );
+
+        // The first job finished, but its output is only published at its
+        // deadline. Changing the sensor doesn't affect it anymore.
+        executor.run_until(5).unwrap();
+        assert_eq!(actuator.get(), 0);
+        sensor.set(5);
+
+        executor.run_until(12).unwrap();
+        assert_eq!(actuator.get(), 2);
+
+        executor.run().unwrap();
+        assert_eq!(actuator.get(), 10);
+        assert_eq!(timer.now(), 20);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_output_of_overlapping_jobs() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let actuator = Port::new(0);
+        let output = actuator.output();
+        let jobs = Rc::new(Cell::new(0));
+        executor.spawn(
+            Task::periodic(2, 5, DelayStrategy::ReturnError, {
+                let output = output.clone();
+                move || {
+                    jobs.set(jobs.get() + 1);
+                    let (output, job) = (output.clone(), jobs.get());
+                    async move { output.set(job) }
+                }
+            })
+            .with_job_limit(2)
+            .with_output(&output),
+        );
+
+        // The second job finished at 2, but the first job's output is
+        // published at 5.
+        executor.run_until(6).unwrap();
+        assert_eq!(actuator.get(), 1);
+        executor.run().unwrap();
+        assert_eq!(actuator.get(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_cyclic_executive() {
+        let timer = TestTimer::default();
+        let runs = Rc::new(Cell::new(0));
+        let mut table = ScheduleTable::new(5);
+        let job = |table: &mut ScheduleTable, name, wcet, duration| {
+            let (timer, runs) = (timer.clone(), runs.clone());
+            table.job(name, wcet, move || {
+                runs.set(runs.get() + 1);
+                compute(timer.clone(), duration)
+            })
+        };
+        let sensor = job(&mut table, "sensor", 2, 1);
+        let control = job(&mut table, "control", 2, 2);
+        table.frame(&[sensor, control]).frame(&[sensor]);
+
+        let mut executive = CyclicExecutive::new(timer.clone(), table).unwrap();
+        assert_eq!(executive.major_frame(), 10);
+        executive.run_major_frames(2).unwrap();
+        assert_eq!(runs.get(), 6);
+        assert_eq!(timer.now(), 20);
+
+        // The logging job takes longer than its WCET.
+        let mut table = ScheduleTable::new(5);
+        let sensor = job(&mut table, "sensor", 2, 1);
+        let logging = job(&mut table, "logging", 1, 7);
+        table.frame(&[sensor]).frame(&[sensor, logging]);
+        let mut executive = CyclicExecutive::new(timer.clone(), table).unwrap();
+        assert_eq!(executive.run_major_frame(), Err(FrameOverrun(1, "logging")));
+
+        let mut table = ScheduleTable::new(5);
+        let control = job(&mut table, "control", 2, 2);
+        table.frame(&[control, control, control]);
+        assert!(matches!(
+            CyclicExecutive::new(timer.clone(), table),
+            Err(ScheduleError::FrameOverloaded(0))
+        ));
+
+        let mut table = ScheduleTable::new(5);
+        table.frame(&[]).frame(&[logging]);
+        assert!(matches!(
+            CyclicExecutive::new(timer.clone(), table),
+            Err(ScheduleError::UnknownJob(1))
+        ));
+
+        // Frames keep their phase even if the executive is run late.
+        let mut table = ScheduleTable::new(5);
+        let sensor = job(&mut table, "sensor", 2, 1);
+        table.frame(&[sensor]);
+        let start = timer.now();
+        let mut executive = CyclicExecutive::new(timer.clone(), table).unwrap();
+        timer.delay(core::time::Duration::from_secs(2));
+        executive.run_major_frames(2).unwrap();
+        assert_eq!(timer.now(), start + 10);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_time_partitions() {
+        let timer = TestTimer::default();
+        let mut executor = PartitionedExecutor::new(timer.clone());
+        let control = executor.add_partition("control");
+        let third_party = executor.add_partition("third party");
+        let logger = executor.add_partition("logger");
+        executor
+            .add_window(control, 5)
+            .add_window(third_party, 2)
+            .add_window(logger, 1);
+
+        let control_runs = Rc::new(Cell::new(Vec::new()));
+        let (runs, control_timer) = (control_runs.clone(), timer.clone());
+        executor.partition(control).spawn(
+            Task::periodic(8, 8, DelayStrategy::ReturnError, move || {
+                let (runs, timer) = (runs.clone(), control_timer.clone());
+                async move {
+                    let mut times = runs.take();
+                    times.push(timer.now());
+                    runs.set(times);
+                    compute(timer, 2).await;
+                }
+            })
+            .with_job_limit(3),
+        );
+        let done = Rc::new(Cell::new(false));
+        let done_clone = done.clone();
+        // Runs for 3 seconds without yielding in a 2 second window.
+        executor.partition(third_party).spawn(Task::new(
+            Deadline::Infinite,
+            DelayStrategy::ReturnError,
+            {
+                let timer = timer.clone();
+                async move {
+                    compute(timer.clone(), 3).await;
+                    compute(timer.clone(), 1).await;
+                    compute(timer, 1).await;
+                    done_clone.set(true);
+                }
+            },
+        ));
+
+        executor.run_major_frames(2);
+        assert_eq!(
+            executor.overruns(),
+            &[WindowOverrun {
+                partition: third_party,
+                by: 1
+            }]
+        );
+        // The overrun is taken from the third party's next window.
+        assert!(!done.get());
+        assert_eq!(timer.now(), 16);
+        executor.run_major_frame();
+        assert!(done.get());
+        assert!(executor.errors().is_empty());
+        // The control partition got its windows in time.
+        assert_eq!(control_runs.take(), [0, 8, 16]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_scope() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let seen = Rc::new(Cell::new(None));
+
+        let (seen_clone, child_timer) = (seen.clone(), timer.clone());
+        let outcomes = executor.block_on(
+            scope(timer.clone(), 10.into(), ScopeMode::CollectAll, |scope| {
+                scope.spawn(async { Ok(1) });
+                scope.spawn(async { Err("broken") });
+                async move {
+                    scope.spawn(async move {
+                        compute(child_timer, 2).await;
+                        seen_clone.set(local::deadline());
+                        Ok(3)
+                    });
+                }
+            }),
+            100.into(),
+        );
+        assert_eq!(
+            outcomes,
+            Ok(Vec::from([Ok(1), Err(ScopeError::Failed("broken")), Ok(3)]))
+        );
+        // Children see the earlier deadline of the scope.
+        assert_eq!(seen.get(), Some(Deadline::Finite(10)));
+
+        let child_timer = timer.clone();
+        let outcomes = executor.block_on(
+            scope(timer.clone(), 20.into(), ScopeMode::FailFast, |scope| {
+                scope.spawn(async move {
+                    compute(child_timer, 1).await;
+                    Ok(1)
+                });
+                scope.spawn(async { Err("broken") });
+                async {}
+            }),
+            100.into(),
+        );
+        assert_eq!(
+            outcomes,
+            Ok(Vec::from([
+                Err(ScopeError::Cancelled),
+                Err(ScopeError::Failed("broken"))
+            ]))
+        );
+
+        let child_timer = timer.clone();
+        let outcomes = executor.block_on(
+            scope(timer.clone(), 10.into(), ScopeMode::CollectAll, |scope| {
+                scope.spawn(async move {
+                    while child_timer.now() < 50 {
+                        compute(child_timer.clone(), 1).await;
+                    }
+                    Ok::<_, ()>(())
+                });
+                async {}
+            }),
+            100.into(),
+        );
+        assert_eq!(outcomes, Ok(Vec::from([Err(ScopeError::MissedDeadline)])));
+        assert_eq!(timer.now(), 10);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_supervisor() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let spawner = executor.spawner();
+        let starts = Rc::new(Cell::new(Vec::new()));
+        let child = |name: &'static str, seconds: u64| {
+            let (starts, timer) = (starts.clone(), timer.clone());
+            move || {
+                let (starts, timer) = (starts.clone(), timer.clone());
+                async move {
+                    let mut names = starts.take();
+                    // The first attempt takes longer.
+                    let seconds = if names.contains(&name) {
+                        seconds
+                    } else {
+                        2 * seconds
+                    };
+                    names.push(name);
+                    starts.set(names);
+                    for _ in 0..seconds {
+                        compute(timer.clone(), 1).await;
+                    }
+                    Ok::<(), ()>(())
+                }
+            }
+        };
+
+        // The filter misses its deadline at 3, so it's restarted along with
+        // the logger, which didn't get to run yet.
+        let supervisor = Supervisor::new(RestartStrategy::RestForOne, 2, 100)
+            .child("sensor", None, child("sensor", 1))
+            .child("filter", Some(3), child("filter", 2))
+            .child("logger", None, child("logger", 3))
+            .run(timer.clone(), spawner.clone());
+        let (task, handle) =
+            Task::joinable(Deadline::Infinite, DelayStrategy::ReturnError, supervisor);
+        executor.spawn(task);
+        assert!(executor.run().is_ok());
+        assert_eq!(handle.try_join(), Some(Ok(Ok(()))));
+        assert_eq!(starts.take(), ["filter", "filter", "sensor", "logger"]);
+        assert_eq!(timer.now(), 13);
+
+        // A child that always fails exceeds the restart intensity of its
+        // supervisor, which escalates to the parent, which gives up as well.
+        let failures = Rc::new(Cell::new(0));
+        let inner = {
+            let (timer, spawner, failures) = (timer.clone(), spawner.clone(), failures.clone());
+            move || {
+                let failures = failures.clone();
+                let child_timer = timer.clone();
+                Supervisor::new(RestartStrategy::OneForOne, 2, 10)
+                    .child("broken", None, move || {
+                        let (failures, timer) = (failures.clone(), child_timer.clone());
+                        async move {
+                            compute(timer, 1).await;
+                            failures.set(failures.get() + 1);
+                            Err::<(), _>("broken")
+                        }
+                    })
+                    .run(timer.clone(), spawner.clone())
+            }
+        };
+        let outer = Supervisor::new(RestartStrategy::OneForAll, 1, 100)
+            .child("inner", None, inner)
+            .run(timer.clone(), spawner);
+        let (task, handle) = Task::joinable(Deadline::Infinite, DelayStrategy::ReturnError, outer);
+        executor.spawn(task);
+        assert!(executor.run().is_ok());
+        assert_eq!(handle.try_join(), Some(Ok(Err(Escalated))));
+        assert_eq!(failures.get(), 6);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_panic_isolation() {
+        let mut executor = Executor::new();
+        let done = Rc::new(Cell::new(false));
+        let done_clone = done.clone();
+        let (faulty, handle) =
+            Task::joinable(Deadline::Infinite, DelayStrategy::ReturnError, async {
+                noop::noop().await;
+                panic!("Sensor unplugged.");
+            });
+        executor.spawn(faulty);
+        executor.spawn(Task::new(
+            Deadline::Infinite,
+            DelayStrategy::ReturnError,
+            async move { done_clone.set(true) },
+        ));
+
+        // The panic only ends the faulty task.
+        assert!(executor.run().is_ok());
+        assert!(done.get());
+        assert!(matches!(
+            handle.try_join(),
+            Some(Err(ExecutorError::TaskPanicked(_, message))) if message == "Sensor unplugged."
+        ));
+        assert_eq!(executor.stats().failed, 1);
+
+        executor.spawn(
+            Task::new(Deadline::Infinite, DelayStrategy::ReturnError, async {
+                panic!("Unrecoverable.");
+            })
+            .propagating_panics(),
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| executor.run()));
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_watchdog() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let watchdog = TestWatchdog::new(timer.clone(), 5);
+        executor.set_watchdog(
+            watchdog.clone(),
+            WatchdogConfig::new(1).critical("control", 3),
+        );
+        // Checks in for a while and then stalls.
+        let control_timer = timer.clone();
+        executor.spawn(
+            Task::new(Deadline::Infinite, DelayStrategy::ReturnError, async move {
+                for i in 0..20 {
+                    compute(control_timer.clone(), 1).await;
+                    if i < 5 {
+                        watchdog::check_in();
+                    }
+                }
+            })
+            .with_name("control"),
+        );
+
+        assert!(executor.run_until(8).is_ok());
+        assert!(!watchdog.expired());
+        assert!(executor.run().is_ok());
+        assert!(watchdog.expired());
+        // The last check-in was at 6, so there were no kicks after 9.
+        assert_eq!(watchdog.kicks(), 10);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_watchdog_while_waiting() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let watchdog = TestWatchdog::new(timer.clone(), 2);
+        executor.set_watchdog(watchdog.clone(), WatchdogConfig::new(1));
+        let periodic = || Task::periodic(10, 10, DelayStrategy::ReturnError, || async {});
+
+        // The executor wakes up for the kicks while it waits for the second
+        // job.
+        executor.spawn(periodic().with_job_limit(2));
+        assert!(executor.run().is_ok());
+        assert!(!watchdog.expired());
+        assert_eq!(watchdog.kicks(), 11);
+
+        // A returned miss stops the kicks until the fault is cleared.
+        let late_timer = timer.clone();
+        executor.spawn(Task::new(
+            (timer.now() + 1).into(),
+            DelayStrategy::ReturnError,
+            async move { compute(late_timer, 2).await },
+        ));
+        assert!(executor.run().is_err());
+        executor.spawn(periodic().with_job_limit(2));
+        assert!(executor.run_for(core::time::Duration::from_secs(5)).is_ok());
+        assert!(watchdog.expired());
+        executor.clear_watchdog_fault();
+        assert!(executor.run().is_ok());
+        assert!(!watchdog.expired());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_checkpoint_supervision() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let escalations = Rc::new(Cell::new(Vec::new()));
+        let escalations_clone = escalations.clone();
+        executor.supervise(
+            SupervisedEntity::new(
+                "control",
+                Escalation::Callback(Box::new(move |task_id| {
+                    let mut task_ids = escalations_clone.take();
+                    task_ids.push(task_id);
+                    escalations_clone.set(task_ids);
+                })),
+            )
+            .alive("read", 10, 1, 2)
+            .deadline("read", "write", 0, 3)
+            .initial("read")
+            .transition("read", "filter")
+            .transition("filter", "write"),
+        );
+        let control_timer = timer.clone();
+        let control = executor.spawn(
+            Task::new(Deadline::Infinite, DelayStrategy::ReturnError, async move {
+                // Filtering takes too long in the second iteration and is
+                // skipped in the third one.
+                for filter_time in [1, 3, 0] {
+                    supervision::checkpoint("read");
+                    compute(control_timer.clone(), 1).await;
+                    if filter_time > 0 {
+                        supervision::checkpoint("filter");
+                        compute(control_timer.clone(), filter_time).await;
+                    }
+                    supervision::checkpoint("write");
+                }
+                // Stalls for two alive cycles.
+                noop::noop().await;
+                compute(control_timer, 20).await;
+            })
+            .with_name("control"),
+        );
+
+        assert!(executor.run().is_ok());
+        let violations: Vec<_> = executor
+            .violations()
+            .iter()
+            .map(|(task, violation)| {
+                assert_eq!(*task, "control");
+                violation.clone()
+            })
+            .collect();
+        assert_eq!(
+            violations,
+            [
+                Violation::Deadline {
+                    from: "read",
+                    to: "write",
+                    elapsed: 4
+                },
+                Violation::Logical {
+                    previous: Some("read"),
+                    checkpoint: "write"
+                },
+                Violation::Alive {
+                    checkpoint: "read",
+                    count: 3
+                },
+                Violation::Alive {
+                    checkpoint: "read",
+                    count: 0
+                },
+            ]
+        );
+        // Both alive violations are found at the same time.
+        assert_eq!(escalations.take(), [control; 3]);
+        assert_eq!(executor.take_violations().len(), 4);
+        assert!(executor.violations().is_empty());
+
+        // Entities are only checked while their task runs, so the finished
+        // task's alive supervision doesn't fail anymore.
+        assert!(executor
+            .run_for(core::time::Duration::from_secs(30))
+            .is_ok());
+        assert!(executor.violations().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_task_ids_and_metadata() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let mut other = Executor::with_timer(timer.clone());
+        let first = executor.spawn(Task::new(
+            Deadline::Infinite,
+            DelayStrategy::ContinueRunning,
+            async {},
+        ));
+        let late = executor.spawn(
+            Task::new(
+                2.into(),
+                DelayStrategy::ReturnError,
+                compute(timer.clone(), 3),
+            )
+            .with_name("late")
+            .with_criticality(Criticality::High)
+            .with_tag("subsystem", "brakes"),
+        );
+        // IDs are only unique within an executor.
+        assert_ne!(first, late);
+        assert_eq!(
+            other.spawn(Task::new(
+                Deadline::Infinite,
+                DelayStrategy::ReturnError,
+                async {}
+            )),
+            first
+        );
+
+        let metadata = executor.metadata(late).unwrap();
+        assert_eq!(metadata.name, Some("late"));
+        assert_eq!(metadata.deadline, Deadline::Finite(2));
+        assert_eq!(metadata.strategy, "ReturnError");
+        assert_eq!(metadata.criticality, Criticality::High);
+        assert_eq!(metadata.tags, [("subsystem", "brakes")]);
+
+        let error = executor.run().unwrap_err();
+        assert_eq!(error, ExecutorError::MissedDeadline(late));
+        assert_eq!(executor.failed_task(), Some(&metadata));
+        assert_eq!(executor.metadata(late), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_introspection() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let logger = executor.spawn(
+            Task::new(10.into(), DelayStrategy::ReturnError, {
+                let timer = timer.clone();
+                async move {
+                    for _ in 0..4 {
+                        compute(timer.clone(), 1).await;
+                    }
+                }
+            })
+            .with_name("logger"),
+        );
+        let (task, handle) = Task::joinable(5.into(), DelayStrategy::ReturnError, {
+            let timer = timer.clone();
+            async move {
+                loop {
+                    compute(timer.clone(), 1).await;
+                }
+            }
+        });
+        let stuck = executor.spawn(task);
+
+        // The stuck task is aborted once the logger is done.
+        assert!(executor.set_deadline(stuck, 20.into()));
+        assert!(executor.suspend(stuck));
+        assert!(executor.run_until(2).is_ok());
+        let tasks = executor.tasks();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].id, logger);
+        assert_eq!(tasks[0].name, Some("logger"));
+        assert_eq!(tasks[0].state, TaskState::Ready);
+        assert_eq!(tasks[0].slack, Some(8));
+        assert_eq!(tasks[0].polls, 2);
+        assert_eq!(tasks[1].state, TaskState::Suspended);
+        assert_eq!(tasks[1].deadline, Deadline::Finite(20));
+        assert_eq!(tasks[1].polls, 0);
+
+        // Without the stuck task, the executor is done once the logger is.
+        assert!(executor.run().is_ok());
+        assert_eq!(executor.tasks().len(), 1);
+        assert!(executor.resume(stuck));
+        assert!(executor.set_strategy(stuck, DelayStrategy::ContinueRunning));
+        assert!(executor.run_until(25).is_ok());
+        assert_eq!(executor.tasks()[0].state, TaskState::Late);
+        assert!(executor.abort(stuck));
+        assert!(!executor.abort(stuck));
+        assert_eq!(
+            handle.try_join(),
+            Some(Err(ExecutorError::Cancelled(stuck)))
+        );
+        assert!(executor.tasks().is_empty());
+    }
+
+    /// Output that can still be read after it was handed to a console.
+    #[cfg(feature = "std")]
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<core::cell::RefCell<Vec<u8>>>);
+
+    #[cfg(feature = "std")]
+    impl std::io::Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_debug_console() {
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer);
+        let worker = executor.spawn(
+            Task::new(
+                Deadline::Infinite,
+                DelayStrategy::ContinueRunning,
+                std::future::pending(),
+            )
+            .with_name("worker"),
+        );
+        executor.suspend(worker);
+        let output = SharedOutput::default();
+        let input = "tasks\ndeadline 1 4500\nbogus\ntrace on\ncancel 1\ncancel 1\nstats\n";
+        executor.spawn_console(Console::new(input.as_bytes(), output.clone()));
+
+        // The console finishes once its input is closed.
+        assert!(executor.run().is_ok());
+        let output = std::string::String::from_utf8(output.0.take()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 8);
+        for expected in [
+            "1 worker Suspended deadline=Infinite slack=None polls=0",
+            "Deadline of 1 is 5.",
+            "Unknown command: bogus",
+            "trace on",
+            "Cancelled 1.",
+            "No task 1.",
+        ] {
+            assert!(lines.contains(&expected), "Missing {:?}", expected);
+        }
+        assert!(lines.iter().any(|line| line.starts_with("2 console Ready")));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("now=0 tasks=1 ")
+                    && line.ends_with(" failed=1 mode=Low"))
+        );
+    }
+
+    #[cfg(all(feature = "std", unix))]
+    #[test]
+    fn test_unix_socket_console() {
+        use std::io::{Read, Write};
+        use std::os::unix::net::UnixStream;
+
+        let name = alloc::format!("console-{}.sock", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        // Waiting for a client doesn't block, and dropping the console stops
+        // it and removes the socket.
+        drop(Console::unix_socket(&path).unwrap());
+        assert!(!path.exists());
+
+        let mut executor = Executor::with_timer(TestTimer::default());
+        executor.spawn_console(Console::unix_socket(&path).unwrap());
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"stats\n").unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        assert!(executor.run().is_ok());
+        let mut output = std::string::String::new();
+        client.read_to_string(&mut output).unwrap();
+        assert!(output.starts_with("now=0 tasks=1 "), "{:?}", output);
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_shutdown() {
+        use core::time::Duration;
+        use futures_util::task::LocalSpawnExt;
+
+        let timer = TestTimer::default();
+        let mut executor = Executor::with_timer(timer.clone());
+        let spawner = executor.spawner();
+        let refused = Rc::new(Cell::new(false));
+        let refused_clone = refused.clone();
+        let quick = executor.spawn(Task::new(10.into(), DelayStrategy::ReturnError, {
+            let timer = timer.clone();
+            async move {
+                compute(timer, 2).await;
+                refused_clone.set(spawner.spawn_local(async {}).is_err());
+            }
+        }));
+        let slow = executor.spawn(Task::new(Deadline::Infinite, DelayStrategy::ReturnError, {
+            let timer = timer.clone();
+            async move {
+                for _ in 0..100 {
+                    compute(timer.clone(), 1).await;
+                }
+            }
+        }));
+        let failing = executor.spawn(Task::new(
+            3.into(),
+            DelayStrategy::ReturnError,
+            compute(timer.clone(), 5),
+        ));
+        let jobs = Rc::new(Cell::new(0));
+        let (jobs_clone, periodic_timer) = (jobs.clone(), timer.clone());
+        let periodic = executor.spawn(Task::periodic(
+            4,
+            8,
+            DelayStrategy::ReturnError,
+            move || {
+                jobs_clone.set(jobs_clone.get() + 1);
+                compute(periodic_timer.clone(), 1)
+            },
+        ));
+        let hook_time = Rc::new(Cell::new(None));
+        let (hook_time_clone, hook_timer) = (hook_time.clone(), timer.clone());
+        executor.on_shutdown(move || hook_time_clone.set(Some(hook_timer.now())));
+
+        let report = executor.shutdown(Duration::from_secs(10));
+        let ids = |tasks: &[crate::task::TaskMetadata]| -> Vec<_> {
+            tasks.iter().map(|task| task.id).collect()
+        };
+        assert_eq!(ids(&report.completed), [quick, periodic]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0.id, failing);
+        assert_eq!(report.failed[0].1, ExecutorError::MissedDeadline(failing));
+        assert_eq!(ids(&report.cancelled), [slow]);
+        // The periodic task only finished the job that was already released.
+        assert_eq!(jobs.get(), 1);
+        assert!(refused.get());
+        assert_eq!(hook_time.get(), Some(10));
+        assert!(report.errors.is_empty());
+
+        // A periodic task waiting for its next job is cancelled.
+        let mut executor = Executor::with_timer(timer.clone());
+        let waiting = executor.spawn(Task::periodic(5, 5, DelayStrategy::ReturnError, {
+            let timer = timer.clone();
+            move || compute(timer.clone(), 1)
+        }));
+        assert!(executor.run_for(Duration::from_secs(2)).is_ok());
+        // A job that is aborted silently doesn't complete either.
+        let aborted = executor.spawn(Task::new(
+            (timer.now() + 1).into(),
+            DelayStrategy::SilentlyAbort,
+            compute(timer.clone(), 3),
+        ));
+        let report = executor.shutdown(Duration::from_secs(10));
+        assert!(report.completed.is_empty());
+        assert!(report.failed.is_empty());
+        assert_eq!(ids(&report.cancelled), [waiting, aborted]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_safe_state() {
+        use core::{cell::RefCell, time::Duration};
+
+        let budgets = SafeStateRegistry::new(3)
+            .handler("outputs", 1, 2, || {})
+            .handler("valves", 2, 2, || {});
+        assert_eq!(
+            SafeState::new(budgets).err(),
+            Some(SafeStateError::BudgetsExceedMaximum {
+                budgets: 4,
+                maximum: 3
+            })
+        );
+        let overflowing = SafeStateRegistry::new(Timestamp::MAX)
+            .handler("outputs", 1, Timestamp::MAX, || {})
+            .handler("valves", 2, 1, || {});
+        assert_eq!(
+            SafeState::new(overflowing).err(),
+            Some(SafeStateError::BudgetsExceedMaximum {
+                budgets: Timestamp::MAX,
+                maximum: Timestamp::MAX
+            })
+        );
+
+        let timer = TestTimer::default();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let handler = |name: &'static str, duration: u64| {
+            let (order, timer) = (order.clone(), timer.clone());
+            move || {
+                order.borrow_mut().push(name);
+                timer.delay(Duration::from_secs(duration));
+            }
+        };
+        let safe_state = SafeState::new(
+            SafeStateRegistry::new(3)
+                .handler("outputs", 1, 1, handler("outputs", 1))
+                .handler("valves", 2, 2, handler("valves", 3)),
+        )
+        .unwrap();
+        let mut executor = Executor::with_timer(timer.clone());
+        executor.set_safe_state(safe_state.clone());
+        executor.spawn(Task::new(
+            1.into(),
+            DelayStrategy::Panic,
+            compute(timer.clone(), 2),
+        ));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| executor.run()));
+        assert!(result.is_err());
+        assert_eq!(*order.borrow(), ["valves", "outputs"]);
+        let report = safe_state.report().unwrap();
+        assert_eq!(report.elapsed, 4);
+        assert_eq!(report.overruns, [("valves", 3)]);
+        assert!(report.exceeded_maximum);
+        // The handlers only run once.
+        assert_eq!(safe_state.enter(&timer), None);
+        assert_eq!(order.borrow().len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_panic_hook() {
+        let entered = Rc::new(Cell::new(false));
+        let entered_clone = entered.clone();
+        let safe_state =
+            SafeState::new(
+                SafeStateRegistry::new(1).handler("outputs", 1, 1, move || entered_clone.set(true)),
+            )
+            .unwrap();
+        safe_state.install_panic_hook(StdTimer);
+
+        // A task's panic is contained, so it doesn't need the safe state.
+        let mut executor = Executor::new();
+        executor.spawn(Task::new(
+            Deadline::Infinite,
+            DelayStrategy::ReturnError,
+            async { panic!("isolated") },
+        ));
+        assert!(executor.run().is_ok());
+        assert!(!entered.get());
+        assert!(safe_state.report().is_none());
+
+        assert!(std::panic::catch_unwind(|| panic!("fatal")).is_err());
+        assert!(entered.get());
+    }
 }
@@ -1,19 +1,42 @@
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 
-pub(crate) struct PriorityQueue<I, P: Ord + Copy> {
+pub(crate) struct PriorityQueue<I: Ord + Copy, P: Ord + Copy> {
     items: UnsafeCell<Vec<(I, P)>>,
+    /// The priority each item was last pushed with.
+    priorities: UnsafeCell<BTreeMap<I, P>>,
 }
 
-impl<I, P: Ord + Copy> PriorityQueue<I, P> {
+impl<I: Ord + Copy, P: Ord + Copy> PriorityQueue<I, P> {
     pub fn new() -> Self {
         Self {
             items: UnsafeCell::new(Vec::new()),
+            priorities: UnsafeCell::new(BTreeMap::new()),
         }
     }
+    /// Queues the item. An item that is already queued only gets the new
+    /// priority, so each item is queued at most once.
     pub fn push(&self, item: I, priority: P) {
         unsafe {
-            (*self.items.get()).push((item, priority));
+            (*self.priorities.get()).insert(item, priority);
+        }
+        let items = unsafe { &mut *self.items.get() };
+        match items.iter_mut().find(|(other, _)| *other == item) {
+            Some(entry) => entry.1 = priority,
+            None => items.push((item, priority)),
+        }
+    }
+    /// Queues the item with the priority it was last pushed with, unless it's
+    /// already queued. Items that were never pushed or were removed are
+    /// ignored.
+    pub fn wake(&self, item: I) {
+        let priority = unsafe { (*self.priorities.get()).get(&item).copied() };
+        let items = unsafe { &mut *self.items.get() };
+        if let Some(priority) = priority {
+            if !items.iter().any(|(other, _)| *other == item) {
+                items.push((item, priority));
+            }
         }
     }
     pub fn pop(&self) -> Option<(I, P)> {
@@ -29,7 +52,14 @@ impl<I, P: Ord + Copy> PriorityQueue<I, P> {
     pub fn is_empty(&self) -> bool {
         unsafe { (*self.items.get()).is_empty() }
     }
+    /// Removes the item and forgets its priority.
+    pub fn remove(&self, item: I) {
+        unsafe {
+            (*self.priorities.get()).remove(&item);
+            (*self.items.get()).retain(|(other, _)| *other != item);
+        }
+    }
 }
 
-unsafe impl<I, P: Ord + Copy> Send for PriorityQueue<I, P> {}
-unsafe impl<I, P: Ord + Copy> Sync for PriorityQueue<I, P> {}
+unsafe impl<I: Ord + Copy, P: Ord + Copy> Send for PriorityQueue<I, P> {}
+unsafe impl<I: Ord + Copy, P: Ord + Copy> Sync for PriorityQueue<I, P> {}
@@ -0,0 +1,39 @@
+use alloc::{boxed::Box, rc::Rc};
+use core::future::Future;
+
+use super::TaskFuture;
+use crate::{time::Timestamp, PartialSink};
+
+/// Replaces a task that missed its deadline with an approximation.
+///
+/// The approximation is created from the last partial result the original
+/// task stored in its [`PartialSink`] and has to finish within its own budget.
+pub struct Approximation {
+    create: Box<dyn Fn() -> TaskFuture>,
+    budget: Timestamp,
+}
+
+impl Approximation {
+    /// The future returned by `approximate` gets `budget` time after the miss
+    /// was detected. If it doesn't finish in time, the executor returns an
+    /// error.
+    pub fn new<T, F>(
+        sink: Rc<PartialSink<T>>,
+        budget: Timestamp,
+        approximate: impl Fn(Option<T>) -> F + 'static,
+    ) -> Self
+    where
+        T: Copy + 'static,
+        F: Future<Output = ()> + 'static,
+    {
+        Self {
+            create: Box::new(move || Box::pin(approximate(sink.get()))),
+            budget,
+        }
+    }
+
+    /// Creates the approximating future and returns it with its deadline.
+    pub(crate) fn start(&self, now: Timestamp) -> (Timestamp, TaskFuture) {
+        (now + self.budget, (self.create)())
+    }
+}
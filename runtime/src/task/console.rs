@@ -0,0 +1,327 @@
+//! A line-based debug console for inspecting a running executor.
+//!
+//! The console runs as a task of the executor it inspects, and its commands
+//! are executed when that task is polled, so it's scheduled like any other
+//! task. It understands these commands:
+//! - `tasks`: lists all tasks
+//! - `stats`: shows statistics of the executor
+//! - `trace on` / `trace off`: reports every poll of a task
+//! - `cancel <id>`: aborts a task
+//! - `deadline <id> <ms>`: sets the deadline of a task to the given number of
+//!   milliseconds from now, rounded up to the timer's resolution
+
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+
+use super::TaskId;
+use crate::time::IntoDuration;
+
+// Without std, there's no console that creates commands.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) enum Command {
+    Tasks,
+    Stats,
+    Trace(bool),
+    Cancel(TaskId),
+    Deadline(TaskId, Duration),
+}
+
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+impl Command {
+    fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: &str| {
+            word.parse::<u64>()
+                .map_err(|_| format!("Not a number: {}", word))
+        };
+        match words.as_slice() {
+            ["tasks"] => Ok(Command::Tasks),
+            ["stats"] => Ok(Command::Stats),
+            ["trace", "on"] => Ok(Command::Trace(true)),
+            ["trace", "off"] => Ok(Command::Trace(false)),
+            ["cancel", id] => Ok(Command::Cancel(TaskId(number(id)?))),
+            ["deadline", id, ms] => {
+                Ok(Command::Deadline(TaskId(number(id)?), number(ms)?.millis()))
+            }
+            _ => Err(format!("Unknown command: {}", line.trim())),
+        }
+    }
+}
+
+/// The state shared between the console task and the executor.
+#[derive(Default)]
+pub(crate) struct Shared {
+    commands: RefCell<VecDeque<Command>>,
+    replies: RefCell<VecDeque<String>>,
+    tracing: Cell<bool>,
+}
+
+impl Shared {
+    pub fn take_commands(&self) -> VecDeque<Command> {
+        self.commands.take()
+    }
+
+    pub fn reply(&self, reply: impl ToString) {
+        self.replies.borrow_mut().push_back(reply.to_string());
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.get()
+    }
+
+    pub fn set_tracing(&self, tracing: bool) {
+        self.tracing.set(tracing);
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::io::Console;
+
+#[cfg(feature = "std")]
+mod io {
+    use alloc::{boxed::Box, rc::Rc, string::String};
+    use core::task::Poll;
+    use futures_util::future::poll_fn;
+    use std::{
+        io::{self, BufRead, BufReader, Read, Write},
+        sync::mpsc::{self, Receiver, Sender, TryRecvError},
+        thread::{self, JoinHandle},
+    };
+
+    use super::{Command, Shared};
+
+    /// The input and output of a debug console. Input is read on a separate
+    /// thread, so the executor doesn't block while waiting for commands.
+    pub struct Console {
+        lines: Receiver<String>,
+        output: Box<dyn Write>,
+        reader: Reader,
+    }
+
+    impl Console {
+        /// Reads commands from `input` until it's closed. If the console is
+        /// dropped before, the reading thread ends after the next line.
+        pub fn new(input: impl Read + Send + 'static, output: impl Write + 'static) -> Self {
+            let (sender, lines) = mpsc::channel();
+            let thread = thread::spawn(move || read_lines(input, sender));
+            Self {
+                lines,
+                output: Box::new(output),
+                reader: Reader {
+                    thread: Some(thread),
+                    stop: None,
+                },
+            }
+        }
+
+        pub fn stdio() -> Self {
+            Self::new(io::stdin(), io::stdout())
+        }
+
+        /// Accepts a client on the socket at the given path in the background.
+        /// Replies before the client connects are dropped.
+        /// The socket is removed once the console is dropped or finished.
+        #[cfg(unix)]
+        pub fn unix_socket(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+            unix::console(path.as_ref())
+        }
+
+        /// Passes commands to the executor and writes its replies. Finishes
+        /// once the input is closed and all commands are answered.
+        pub(crate) async fn run(self, shared: Rc<Shared>) {
+            let Console {
+                lines,
+                mut output,
+                reader,
+            } = self;
+            poll_fn(move |_| {
+                // Stops the reading thread when the task is dropped.
+                let _ = &reader;
+                let mut closed = false;
+                loop {
+                    match lines.try_recv() {
+                        Ok(line) if line.trim().is_empty() => {}
+                        Ok(line) => match Command::parse(&line) {
+                            Ok(command) => shared.commands.borrow_mut().push_back(command),
+                            Err(error) => shared.reply(error),
+                        },
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+                for reply in shared.replies.take() {
+                    let _ = writeln!(output, "{}", reply);
+                }
+                let _ = output.flush();
+                if closed && shared.commands.borrow().is_empty() {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    }
+
+    fn read_lines(input: impl Read, sender: Sender<String>) {
+        for line in BufReader::new(input).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// The thread reading the input of a console, which is joined when the
+    /// console is dropped.
+    struct Reader {
+        thread: Option<JoinHandle<()>>,
+        /// Interrupts the thread, if its input allows that.
+        stop: Option<Box<dyn FnOnce()>>,
+    }
+
+    impl Drop for Reader {
+        fn drop(&mut self) {
+            let thread = match self.thread.take() {
+                Some(thread) => thread,
+                None => return,
+            };
+            match self.stop.take() {
+                Some(stop) => {
+                    stop();
+                    let _ = thread.join();
+                }
+                None if thread.is_finished() => {
+                    let _ = thread.join();
+                }
+                // Joining would block until the next line. The thread ends then,
+                // because nobody receives the line anymore.
+                None => {}
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    mod unix {
+        use alloc::{boxed::Box, sync::Arc};
+        use core::{
+            sync::atomic::{AtomicBool, Ordering},
+            time::Duration,
+        };
+        use std::{
+            fs,
+            io::{self, Write},
+            net::Shutdown,
+            os::unix::net::{UnixListener, UnixStream},
+            path::Path,
+            sync::{mpsc, Mutex},
+            thread,
+        };
+
+        use super::{read_lines, Console, Reader};
+
+        /// How often the listener checks whether the console was dropped while
+        /// no client is connected.
+        const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+        pub fn console(path: &Path) -> io::Result<Console> {
+            let listener = UnixListener::bind(path)?;
+            if let Err(error) = listener.set_nonblocking(true) {
+                let _ = fs::remove_file(path);
+                return Err(error);
+            }
+            let path = path.to_path_buf();
+            let stop = Arc::new(AtomicBool::new(false));
+            let connection: Arc<Mutex<Option<UnixStream>>> = Arc::default();
+            let (sender, lines) = mpsc::channel();
+            let (streams, receiver) = mpsc::channel();
+            let thread = thread::spawn({
+                let (stop, connection) = (stop.clone(), connection.clone());
+                move || {
+                    let input = match accept(&listener, &stop) {
+                        Some(input) => input,
+                        None => return,
+                    };
+                    // Lets the console shut the connection down to stop reading.
+                    match connection.lock() {
+                        Ok(mut connection) if !stop.load(Ordering::Acquire) => {
+                            *connection = input.try_clone().ok();
+                        }
+                        _ => return,
+                    }
+                    if let Ok(output) = input.try_clone() {
+                        let _ = streams.send(output);
+                    }
+                    read_lines(input, sender);
+                }
+            });
+            Ok(Console {
+                lines,
+                output: Box::new(Client {
+                    streams: receiver,
+                    stream: None,
+                }),
+                reader: Reader {
+                    thread: Some(thread),
+                    stop: Some(Box::new(move || {
+                        stop.store(true, Ordering::Release);
+                        let stream = connection.lock().ok().and_then(|mut stream| stream.take());
+                        if let Some(stream) = stream {
+                            let _ = stream.shutdown(Shutdown::Both);
+                        }
+                        let _ = fs::remove_file(&path);
+                    })),
+                },
+            })
+        }
+
+        /// Waits for a client until the console is dropped.
+        fn accept(listener: &UnixListener, stop: &AtomicBool) -> Option<UnixStream> {
+            while !stop.load(Ordering::Acquire) {
+                match listener.accept() {
+                    Ok((stream, _)) => return stream.set_nonblocking(false).ok().map(|()| stream),
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_INTERVAL)
+                    }
+                    Err(_) => return None,
+                }
+            }
+            None
+        }
+
+        /// The output to a client that may not have connected yet.
+        struct Client {
+            streams: mpsc::Receiver<UnixStream>,
+            stream: Option<UnixStream>,
+        }
+
+        impl Write for Client {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.stream.is_none() {
+                    self.stream = self.streams.try_recv().ok();
+                }
+                match &mut self.stream {
+                    Some(stream) => stream.write(buf),
+                    None => Ok(buf.len()),
+                }
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                match &mut self.stream {
+                    Some(stream) => stream.flush(),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::time::Timestamp;
+
+/// How important it is that a task meets its deadline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Criticality {
+    /// Best-effort work like telemetry. May be dropped or degraded to keep
+    /// high-criticality tasks on time.
+    #[default]
+    Low,
+
+    /// Safety-relevant work that always has to meet its deadline.
+    High,
+}
+
+/// Execution time estimates of a task.
+///
+/// The optimistic budget is what the task needs in normal operation, the
+/// pessimistic budget is a safe upper bound (its worst-case execution time).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionBudget {
+    pub optimistic: Timestamp,
+    pub pessimistic: Timestamp,
+}
+
+impl ExecutionBudget {
+    pub fn new(optimistic: Timestamp, pessimistic: Timestamp) -> Self {
+        assert!(
+            optimistic <= pessimistic,
+            "The optimistic budget can't be larger than the pessimistic one."
+        );
+        Self {
+            optimistic,
+            pessimistic,
+        }
+    }
+}
+
+/// The mode the executor is currently running in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CriticalityMode {
+    /// All tasks are scheduled by their deadlines.
+    Low,
+
+    /// A high-criticality task overran its optimistic budget. Only
+    /// high-criticality tasks are guaranteed to meet their deadlines until the
+    /// executor returns to [`CriticalityMode::Low`].
+    High,
+}
+
+/// What happens to low-criticality tasks while the executor is in
+/// [`CriticalityMode::High`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LowCriticalityPolicy {
+    /// Removes all low-criticality tasks from the executor.
+    Drop,
+
+    /// Keeps low-criticality tasks, but only runs them in the background and
+    /// ignores their deadlines until the executor returns to normal mode.
+    Degrade,
+}
@@ -0,0 +1,160 @@
+//! A cyclic executive that runs jobs according to a static schedule table.
+//!
+//! The table consists of minor frames of equal length that together form the
+//! major frame, which is repeated. Each minor frame runs a fixed sequence of
+//! jobs, one after another. Whether the jobs of each frame fit into it
+//! according to their WCETs is verified when the executive is created.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    future::Future,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures_util::task::noop_waker_ref;
+
+use super::TaskFuture;
+use crate::time::{Timer, Timestamp};
+
+/// Identifies a job of a [`ScheduleTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobId(usize);
+
+struct Job {
+    name: &'static str,
+    wcet: Timestamp,
+    create: Box<dyn Fn() -> TaskFuture>,
+}
+
+/// An offline schedule: which jobs run in which minor frame.
+pub struct ScheduleTable {
+    minor_frame: Timestamp,
+    jobs: Vec<Job>,
+    frames: Vec<Vec<JobId>>,
+}
+
+impl ScheduleTable {
+    pub fn new(minor_frame: Timestamp) -> Self {
+        Self {
+            minor_frame,
+            jobs: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Declares a job that can be scheduled in frames. Each time it's
+    /// scheduled, a new future is created.
+    pub fn job<F: Future<Output = ()> + 'static>(
+        &mut self,
+        name: &'static str,
+        wcet: Timestamp,
+        create: impl Fn() -> F + 'static,
+    ) -> JobId {
+        self.jobs.push(Job {
+            name,
+            wcet,
+            create: Box::new(move || Box::pin(create())),
+        });
+        JobId(self.jobs.len() - 1)
+    }
+
+    /// Appends a minor frame that runs the given jobs in order.
+    pub fn frame(&mut self, jobs: &[JobId]) -> &mut Self {
+        self.frames.push(jobs.to_vec());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// The table has no frames or its minor frames have no length.
+    Empty,
+    /// The WCETs of the jobs in the minor frame with the given index add up
+    /// to more than the length of a minor frame.
+    FrameOverloaded(usize),
+    /// The minor frame with the given index contains a job of another table.
+    UnknownJob(usize),
+}
+
+/// The job with the given name was still running at the end of the minor
+/// frame with the given index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameOverrun(pub usize, pub &'static str);
+
+pub struct CyclicExecutive<T: Timer> {
+    timer: T,
+    table: ScheduleTable,
+    /// When the next major frame starts.
+    start: Timestamp,
+}
+
+impl<T: Timer> CyclicExecutive<T> {
+    /// Verifies that the table fits the declared WCETs. The first major frame
+    /// starts now.
+    pub fn new(timer: T, table: ScheduleTable) -> Result<Self, ScheduleError> {
+        if table.frames.is_empty() || table.minor_frame == 0 {
+            return Err(ScheduleError::Empty);
+        }
+        for (index, frame) in table.frames.iter().enumerate() {
+            if frame.iter().any(|job| job.0 >= table.jobs.len()) {
+                return Err(ScheduleError::UnknownJob(index));
+            }
+            let demand: Timestamp = frame.iter().map(|job| table.jobs[job.0].wcet).sum();
+            if demand > table.minor_frame {
+                return Err(ScheduleError::FrameOverloaded(index));
+            }
+        }
+        let start = timer.now();
+        Ok(Self {
+            timer,
+            table,
+            start,
+        })
+    }
+
+    pub fn major_frame(&self) -> Timestamp {
+        self.table.minor_frame * self.table.frames.len() as Timestamp
+    }
+
+    /// Runs the major frame the given number of times.
+    pub fn run_major_frames(&mut self, count: u64) -> Result<(), FrameOverrun> {
+        for _ in 0..count {
+            self.run_major_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Runs all minor frames once. Major frames follow each other without
+    /// gaps, so the frames keep their phase even if this is called late.
+    pub fn run_major_frame(&mut self) -> Result<(), FrameOverrun> {
+        let start = self.start;
+        self.start += self.major_frame();
+        for index in 0..self.table.frames.len() {
+            let end = start + (index as Timestamp + 1) * self.table.minor_frame;
+            self.run_minor_frame(index, end)?;
+            let now = self.timer.now();
+            if now < end {
+                self.timer.delay(Duration::from_secs(end - now));
+            }
+        }
+        Ok(())
+    }
+
+    fn run_minor_frame(&mut self, index: usize, end: Timestamp) -> Result<(), FrameOverrun> {
+        let mut context = Context::from_waker(noop_waker_ref());
+        for job in &self.table.frames[index] {
+            let job = &self.table.jobs[job.0];
+            let mut future = (job.create)();
+            loop {
+                let poll = future.as_mut().poll(&mut context);
+                if self.timer.now() > end {
+                    return Err(FrameOverrun(index, job.name));
+                }
+                if let Poll::Ready(()) = poll {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
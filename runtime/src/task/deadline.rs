@@ -2,7 +2,7 @@ use core::ops::Add;
 
 use crate::time::Timestamp;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum Deadline {
     Finite(Timestamp),
     Infinite,
@@ -27,13 +27,7 @@ impl Eq for Deadline {}
 
 impl PartialOrd for Deadline {
     fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
-        use core::cmp::Ordering::{Greater, Less};
-        match (self, other) {
-            (Deadline::Finite(left), Deadline::Finite(right)) => left.partial_cmp(right),
-            (Deadline::Finite(_), Deadline::Infinite) => Some(Less),
-            (Deadline::Infinite, Deadline::Finite(_)) => Some(Greater),
-            (Deadline::Infinite, Deadline::Infinite) => None,
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -1,39 +1,168 @@
+use super::console;
+use super::criticality::{Criticality, CriticalityMode, LowCriticalityPolicy};
 use super::deadline::Deadline;
+use super::firm::Escalation;
+use super::graph::{GraphHandle, TaskGraph};
+use super::ports::Publication;
+use super::safe_state::SafeState;
+use super::server::{Server, ServerId};
+use super::spawner::{SpawnPolicy, Spawner};
+use super::supervision::{SupervisedEntity, Violation};
+use super::watchdog::{Watchdog, WatchdogConfig, WatchdogState};
 use super::Task;
 use crate::priority_queue::PriorityQueue;
 use crate::task::DelayStrategy;
-use crate::task::TaskId;
+use crate::task::{TaskId, TaskMetadata};
 use crate::time::Timestamp;
-use alloc::collections::BTreeMap;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::future::Future;
 use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use futures_util::task::{FutureObj, LocalFutureObj, LocalSpawn, Spawn, SpawnError};
 
 #[cfg(feature = "std")]
 use crate::time::StdTimer;
 use crate::time::Timer;
 
-#[derive(Debug)]
+/// How many supervision violations the executor keeps.
+pub const MAX_VIOLATIONS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExecutorError {
-    MissedDeadline(u64),
+    MissedDeadline(TaskId),
+    /// A task ran longer than its pessimistic execution budget.
+    BudgetOverrun(TaskId),
+    /// A task violated its (m,k)-firm constraint.
+    ConstraintViolated(TaskId),
+    /// A task missed its deadline or failed more often than it may be
+    /// restarted.
+    RestartsExhausted(TaskId),
+    /// A task was removed from the executor before it finished.
+    Cancelled(TaskId),
+    /// A task violated its checkpoint supervision.
+    SupervisionFailed(TaskId),
+    /// A task panicked with the given message. Panics are only caught on
+    /// `std` and reported through the task's handle, not by `run`.
+    TaskPanicked(TaskId, String),
+    /// A task was spawned into a server with an index the executor doesn't
+    /// have. An ID of another executor's server isn't detected otherwise.
+    ServerOutOfRange(TaskId),
+}
+
+impl ExecutorError {
+    /// The ID of the task that caused the error.
+    pub fn task_id(&self) -> TaskId {
+        match self {
+            ExecutorError::MissedDeadline(task_id)
+            | ExecutorError::BudgetOverrun(task_id)
+            | ExecutorError::ConstraintViolated(task_id)
+            | ExecutorError::RestartsExhausted(task_id)
+            | ExecutorError::Cancelled(task_id)
+            | ExecutorError::SupervisionFailed(task_id)
+            | ExecutorError::TaskPanicked(task_id, _)
+            | ExecutorError::ServerOutOfRange(task_id) => *task_id,
+        }
+    }
+}
+
+/// What a task is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// The task waits to be polled.
+    Ready,
+    /// The task waits for the release of its next job or for the budget of
+    /// its server.
+    Waiting,
+    /// The task's deadline passed.
+    Late,
+    /// The task was [suspended](Executor::suspend) and isn't polled until it's
+    /// resumed.
+    Suspended,
+}
+
+/// The state of a task at some point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskSnapshot {
+    pub id: TaskId,
+    pub name: Option<&'static str>,
+    pub state: TaskState,
+    pub deadline: Deadline,
+    /// The time until the deadline. `None` for tasks without deadline.
+    pub slack: Option<Timestamp>,
+    /// How often the task was polled.
+    pub polls: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutorStats {
+    pub now: Timestamp,
+    pub tasks: usize,
+    /// How often tasks were polled in total.
+    pub polls: u64,
+    /// How many tasks were removed because of an error or aborted.
+    pub failed: u64,
+    pub mode: CriticalityMode,
+}
+
+/// What happened to the tasks during a [`shutdown`](Executor::shutdown).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Tasks that finished before the drain deadline.
+    pub completed: Vec<TaskMetadata>,
+    /// Tasks that caused an error before the drain deadline.
+    pub failed: Vec<(TaskMetadata, ExecutorError)>,
+    /// Tasks that didn't finish otherwise: Tasks that were still running at
+    /// the drain deadline, periodic tasks that were waiting for their next
+    /// job, and tasks that were aborted or dropped.
+    pub cancelled: Vec<TaskMetadata>,
+    /// Errors that couldn't be attributed to a task.
+    pub errors: Vec<ExecutorError>,
 }
 
 pub struct Executor<T: Timer> {
     tasks: BTreeMap<TaskId, Task>,
     task_queue: Arc<PriorityQueue<TaskId, Timestamp>>,
     waker_cache: BTreeMap<TaskId, Waker>,
+    /// Tasks that are waiting for the release of their next job.
+    releases: BTreeMap<TaskId, Timestamp>,
+    /// Tasks whose server is throttled, until the server's replenishment.
+    throttled: BTreeMap<TaskId, Timestamp>,
     timer: T,
+    mode: CriticalityMode,
+    low_criticality_policy: LowCriticalityPolicy,
+    spawner: Spawner,
+    servers: Vec<Server>,
+    /// Outputs of finished jobs that wait for their logical deadline.
+    publications: Vec<(Timestamp, Publication)>,
+    watchdog: Option<WatchdogState>,
+    supervised: Vec<SupervisedEntity>,
+    /// The most recent violations, at most [`MAX_VIOLATIONS`].
+    violations: Vec<(&'static str, Violation)>,
+    /// The ID the next spawned task gets.
+    next_id: u64,
+    failed_task: Option<TaskMetadata>,
+    suspended: BTreeSet<TaskId>,
+    polls: u64,
+    failed: u64,
+    /// The debug console's task and the state it shares with the executor.
+    console: Option<(TaskId, Rc<console::Shared>)>,
+    shutdown_hooks: Vec<Box<dyn FnOnce()>>,
+    /// Tasks that finished during a shutdown.
+    completed: Option<Vec<TaskMetadata>>,
+    safe_state: Option<SafeState>,
 }
 
 #[cfg(feature = "std")]
 impl Default for Executor<StdTimer> {
     fn default() -> Self {
-        Self {
-            tasks: Default::default(),
-            task_queue: Arc::new(PriorityQueue::new()),
-            waker_cache: Default::default(),
-            timer: Default::default(),
-        }
+        Self::with_timer(Default::default())
     }
 }
 
@@ -47,69 +176,629 @@ impl Executor<StdTimer> {
 impl<T: Timer> Executor<T> {
     #[cfg(not(feature = "std"))]
     pub fn new(timer: T) -> Executor<T> {
+        Self::with_timer(timer)
+    }
+
+    pub fn with_timer(timer: T) -> Executor<T> {
         Self {
             tasks: BTreeMap::new(),
             task_queue: Arc::new(PriorityQueue::new()),
             waker_cache: BTreeMap::new(),
+            releases: BTreeMap::new(),
+            throttled: BTreeMap::new(),
             timer,
+            mode: CriticalityMode::Low,
+            low_criticality_policy: LowCriticalityPolicy::Drop,
+            spawner: Spawner::default(),
+            servers: Vec::new(),
+            publications: Vec::new(),
+            watchdog: None,
+            supervised: Vec::new(),
+            violations: Vec::new(),
+            next_id: 1,
+            failed_task: None,
+            suspended: BTreeSet::new(),
+            polls: 0,
+            failed: 0,
+            console: None,
+            shutdown_hooks: Vec::new(),
+            completed: None,
+            safe_state: None,
+        }
+    }
+
+    pub fn mode(&self) -> CriticalityMode {
+        self.mode
+    }
+
+    /// Configures what happens to low-criticality tasks when a high-criticality
+    /// task overruns its optimistic budget.
+    pub fn set_low_criticality_policy(&mut self, policy: LowCriticalityPolicy) {
+        self.low_criticality_policy = policy;
+    }
+
+    /// A handle for spawning futures onto this executor.
+    pub fn spawner(&self) -> Spawner {
+        self.spawner.clone()
+    }
+
+    /// Configures the deadline and strategy of futures spawned through the
+    /// [`Spawn`] and [`LocalSpawn`] traits.
+    pub fn set_spawn_policy(&mut self, policy: SpawnPolicy) {
+        self.spawner.set_policy(policy);
+    }
+
+    /// Spawns the tasks created through the [`Spawner`].
+    fn accept_spawned(&mut self) {
+        for task in self.spawner.take_spawned() {
+            self.spawn(task);
+        }
+    }
+
+    /// Spawns a task graph. Its stages are released as soon as their inputs
+    /// are available.
+    pub fn spawn_graph(&mut self, graph: TaskGraph) -> GraphHandle {
+        let (handle, roots) = graph.start(self.timer.now(), self.spawner());
+        for task in roots {
+            self.spawn(task);
+        }
+        handle
+    }
+
+    /// Runs the executor until the given future completes and returns its
+    /// output.
+    pub fn block_on<F: Future + 'static>(
+        &mut self,
+        future: F,
+        deadline: Deadline,
+    ) -> Result<F::Output, ExecutorError> {
+        let (task, handle) = Task::joinable(deadline, DelayStrategy::ReturnError, future);
+        let task_id = self.spawn(task);
+        while !handle.is_finished() && self.step(None)? {}
+        handle
+            .try_join()
+            .unwrap_or(Err(ExecutorError::Cancelled(task_id)))
+    }
+
+    /// Adds the task to the executor and returns the ID it got. A task in a
+    /// server of another executor fails right away, see
+    /// [`try_spawn`](Self::try_spawn).
+    pub fn spawn(&mut self, task: Task) -> TaskId {
+        match self.try_spawn(task) {
+            Ok(task_id) => task_id,
+            Err(error) => error.task_id(),
+        }
+    }
+
+    /// Adds the task to the executor and returns the ID it got, or fails the
+    /// task with [`ExecutorError::ServerOutOfRange`] if the executor doesn't
+    /// have a server with its server's index.
+    pub fn try_spawn(&mut self, mut task: Task) -> Result<TaskId, ExecutorError> {
+        let task_id = TaskId(self.next_id);
+        self.next_id += 1;
+        task.id = task_id;
+        if let Some(join) = &task.join {
+            join.assign(task_id);
+        }
+        if matches!(task.server, Some(server) if server.0 >= self.servers.len()) {
+            let error = ExecutorError::ServerOutOfRange(task_id);
+            self.failed += 1;
+            task.fail(&error);
+            return Err(error);
+        }
+        let now = self.timer.now();
+        task.created = now;
+        let deadline: Deadline = match task.relative_deadline.take() {
+            Some(relative_deadline) => {
+                task.deadline = (now + relative_deadline).into();
+                task.deadline
+            }
+            None => task.deadline,
+        };
+        if let Some(server) = task.server {
+            if !self
+                .tasks
+                .values()
+                .any(|other| other.server == Some(server))
+            {
+                self.servers[server.0].activate(now);
+            }
+        }
+        if let Some(periodic) = &mut task.periodic {
+            // The first job is released right away.
+            task.deadline = (now + periodic.relative_deadline).into();
+            periodic.next_release = now + periodic.period;
+            self.releases.insert(task_id, now);
+        } else {
+            task.ports.sample();
+            let deadline = match task.server {
+                Some(server) => self.servers[server.0].deadline.into(),
+                None => deadline,
+            };
+            self.task_queue
+                .push(task_id, deadline.to_scheduling_timestamp(&self.timer));
+        }
+        self.tasks.insert(task_id, task);
+        Ok(task_id)
+    }
+
+    pub fn metadata(&self, task_id: TaskId) -> Option<TaskMetadata> {
+        self.tasks.get(&task_id).map(Task::metadata)
+    }
+
+    /// The metadata of the task that caused the last error.
+    pub fn failed_task(&self) -> Option<&TaskMetadata> {
+        self.failed_task.as_ref()
+    }
+
+    pub fn stats(&self) -> ExecutorStats {
+        ExecutorStats {
+            now: self.timer.now(),
+            tasks: self.tasks.len(),
+            polls: self.polls,
+            failed: self.failed,
+            mode: self.mode,
         }
     }
 
-    pub fn spawn(&mut self, task: Task) {
-        let task_id = task.id;
-        let deadline: Deadline = task.deadline + self.timer.now();
-        if self.tasks.insert(task.id, task).is_some() {
-            panic!("A task with the same ID already exists.");
+    /// Spawns a debug console that inspects this executor.
+    #[cfg(feature = "std")]
+    pub fn spawn_console(&mut self, console: console::Console) -> TaskId {
+        let shared = Rc::new(console::Shared::default());
+        let task_id = self.spawn(
+            Task::new(
+                Deadline::Infinite,
+                DelayStrategy::ContinueRunning,
+                console.run(shared.clone()),
+            )
+            .with_name("console"),
+        );
+        self.console = Some((task_id, shared));
+        task_id
+    }
+
+    /// Stops tracing for a console whose task is gone, since nobody would
+    /// read the replies anymore.
+    fn forget_finished_console(&mut self) {
+        if matches!(&self.console, Some((console, _)) if !self.tasks.contains_key(console)) {
+            self.console = None;
         }
-        self.task_queue
-            .push(task_id, deadline.to_scheduling_timestamp(&self.timer));
     }
 
-    fn run_ready_tasks(&mut self) -> Result<(), ExecutorError> {
-        while let Some((task_id, _)) = self.task_queue.pop() {
-            let task = match self.tasks.get_mut(&task_id) {
-                Some(task) => task,
+    /// Executes the commands the console received while it was just polled,
+    /// so they take up the console task's time instead of other tasks'.
+    fn execute_console_commands(&mut self, polled: TaskId) {
+        let shared = match &self.console {
+            Some((console, shared)) if *console == polled => shared.clone(),
+            _ => return,
+        };
+        for command in shared.take_commands() {
+            match command {
+                console::Command::Tasks => {
+                    for task in self.tasks() {
+                        shared.reply(format!(
+                            "{} {} {:?} deadline={:?} slack={:?} polls={}",
+                            task.id.0,
+                            task.name.unwrap_or("-"),
+                            task.state,
+                            task.deadline,
+                            task.slack,
+                            task.polls
+                        ));
+                    }
+                }
+                console::Command::Stats => {
+                    let stats = self.stats();
+                    shared.reply(format!(
+                        "now={} tasks={} polls={} failed={} mode={:?}",
+                        stats.now, stats.tasks, stats.polls, stats.failed, stats.mode
+                    ));
+                }
+                console::Command::Trace(tracing) => {
+                    shared.set_tracing(tracing);
+                    shared.reply(if tracing { "trace on" } else { "trace off" });
+                }
+                console::Command::Cancel(task_id) => {
+                    if self.abort(task_id) {
+                        shared.reply(format!("Cancelled {}.", task_id.0));
+                    } else {
+                        shared.reply(format!("No task {}.", task_id.0));
+                    }
+                }
+                console::Command::Deadline(task_id, time) => {
+                    // Timestamps are seconds, so the deadline is rounded up.
+                    let seconds = time.as_secs() + u64::from(time.subsec_nanos() > 0);
+                    let deadline = self.timer.now() + seconds;
+                    if self.set_deadline(task_id, deadline.into()) {
+                        shared.reply(format!("Deadline of {} is {}.", task_id.0, deadline));
+                    } else {
+                        shared.reply(format!("No task {}.", task_id.0));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Registers a function that runs during [`shutdown`](Executor::shutdown)
+    /// before the remaining tasks are cancelled.
+    pub fn on_shutdown(&mut self, hook: impl FnOnce() + 'static) {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// Stops the executor: New tasks are refused, but the current jobs may
+    /// finish within the given drain time. Periodic tasks don't release
+    /// further jobs. Afterwards, the shutdown hooks run and the remaining
+    /// tasks are cancelled.
+    pub fn shutdown(mut self, drain: Duration) -> ShutdownReport {
+        self.accept_spawned();
+        self.spawner.close();
+        let now = self.timer.now();
+        let drain_deadline = now + drain.as_secs();
+        let mut report = ShutdownReport::default();
+        self.failed_task = None;
+        self.completed = Some(Vec::new());
+        let initial: Vec<TaskMetadata> = self.tasks.values().map(Task::metadata).collect();
+
+        let mut waiting = Vec::new();
+        for (task_id, task) in &mut self.tasks {
+            if let Some(periodic) = &mut task.periodic {
+                periodic.remaining_jobs = Some(0);
+                let pending = matches!(self.releases.get(task_id), Some(release) if *release > now);
+                if pending && task.executed == 0 {
+                    waiting.push(*task_id);
+                }
+            }
+        }
+        for task_id in waiting {
+            self.remove_task(task_id);
+        }
+
+        while let Err(error) = self.run_until(drain_deadline) {
+            let metadata = self
+                .failed_task
+                .take()
+                .filter(|task| task.id == error.task_id())
+                .or_else(|| {
+                    initial
+                        .iter()
+                        .find(|task| task.id == error.task_id())
+                        .cloned()
+                });
+            match metadata {
+                Some(metadata) => report.failed.push((metadata, error)),
+                None => report.errors.push(error),
+            }
+        }
+        for hook in core::mem::take(&mut self.shutdown_hooks) {
+            hook();
+        }
+        let remaining: Vec<TaskId> = self.tasks.keys().copied().collect();
+        for task_id in remaining {
+            self.abort(task_id);
+        }
+        report.completed = self.completed.take().unwrap_or_default();
+        report.completed.sort_by_key(|task| task.id);
+        // Tasks that were aborted or dropped without an error didn't finish
+        // either.
+        report.cancelled = initial
+            .into_iter()
+            .filter(|task| {
+                !report.failed.iter().any(|(failed, _)| failed.id == task.id)
+                    && !report
+                        .completed
+                        .iter()
+                        .any(|completed| completed.id == task.id)
+            })
+            .collect();
+        report
+    }
+
+    /// A snapshot of all tasks, ordered by their IDs.
+    pub fn tasks(&self) -> Vec<TaskSnapshot> {
+        let now = self.timer.now();
+        self.tasks
+            .values()
+            .map(|task| {
+                let state = if self.suspended.contains(&task.id) {
+                    TaskState::Suspended
+                } else if self.releases.contains_key(&task.id)
+                    || self.throttled.contains_key(&task.id)
+                {
+                    TaskState::Waiting
+                } else if task.deadline <= now.into() {
+                    TaskState::Late
+                } else {
+                    TaskState::Ready
+                };
+                TaskSnapshot {
+                    id: task.id,
+                    name: task.name,
+                    state,
+                    deadline: task.deadline,
+                    slack: match task.deadline {
+                        Deadline::Finite(deadline) => Some(deadline.saturating_sub(now)),
+                        Deadline::Infinite => None,
+                    },
+                    polls: task.polls,
+                }
+            })
+            .collect()
+    }
+
+    /// Changes the deadline of the task's current job. Returns whether the
+    /// task exists.
+    pub fn set_deadline(&mut self, task_id: TaskId, deadline: Deadline) -> bool {
+        let task = match self.tasks.get_mut(&task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        task.deadline = deadline;
+        self.reschedule(task_id);
+        true
+    }
+
+    /// Changes what happens when the task misses its deadline. Returns whether
+    /// the task exists.
+    pub fn set_strategy(&mut self, task_id: TaskId, strategy: DelayStrategy) -> bool {
+        match self.tasks.get_mut(&task_id) {
+            Some(task) => {
+                task.behavior = strategy;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops polling the task until it's resumed. Returns whether the task
+    /// exists.
+    pub fn suspend(&mut self, task_id: TaskId) -> bool {
+        if !self.tasks.contains_key(&task_id) {
+            return false;
+        }
+        self.suspended.insert(task_id);
+        true
+    }
+
+    /// Returns whether the task was suspended.
+    pub fn resume(&mut self, task_id: TaskId) -> bool {
+        let resumed = self.suspended.remove(&task_id);
+        if resumed {
+            self.reschedule(task_id);
+        }
+        resumed
+    }
+
+    /// Removes the task. Its [`JoinHandle`](super::join::JoinHandle) reports
+    /// it as cancelled. Returns whether the task existed.
+    pub fn abort(&mut self, task_id: TaskId) -> bool {
+        let exists = self.tasks.contains_key(&task_id);
+        if exists {
+            self.remove_failed(ExecutorError::Cancelled(task_id));
+        }
+        exists
+    }
+
+    /// Queues the task with its current scheduling deadline, unless it waits
+    /// for a release anyway. A queued task only gets the new priority.
+    fn reschedule(&mut self, task_id: TaskId) {
+        if self.releases.contains_key(&task_id) {
+            return;
+        }
+        if let Some(task) = self.tasks.get(&task_id) {
+            self.task_queue.push(
+                task_id,
+                scheduling_deadline(&self.servers, task).to_scheduling_timestamp(&self.timer),
+            );
+        }
+    }
+
+    /// Makes a single scheduling decision: Releases due jobs and polls the
+    /// task with the earliest deadline once. Returns whether a task was polled.
+    ///
+    /// A task that causes an error is removed, so the executor can continue
+    /// running the other tasks afterwards.
+    pub fn poll_once(&mut self) -> Result<bool, ExecutorError> {
+        self.accept_spawned();
+        let now = self.timer.now();
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.service(now);
+        }
+        self.check_supervision(now)
+            .map_err(|error| self.remove_failed(error))?;
+        self.forget_finished_console();
+        self.publish_due_outputs(now);
+        for server in &mut self.servers {
+            server.replenish(now);
+        }
+        self.unthrottle_tasks(now);
+        self.release_due_tasks()
+            .map_err(|error| self.remove_failed(error))?;
+        self.inherit_deadlines();
+        let task_id = loop {
+            let task_id = match self.task_queue.pop() {
+                Some((task_id, _)) => task_id,
+                None => return Ok(false),
+            };
+            if self.releases.contains_key(&task_id) || self.suspended.contains(&task_id) {
+                continue;
+            }
+            let server = match self.tasks.get(&task_id) {
+                Some(task) => task.server,
                 None => continue,
             };
-            let waker = self
-                .waker_cache
-                .entry(task_id)
-                .or_insert_with(|| TaskWaker::new(task_id, task.deadline, self.task_queue.clone()));
-            let mut context = Context::from_waker(waker);
-
-            match task.poll(&mut context) {
-                Poll::Ready(()) => {
-                    self.tasks.remove(&task_id);
-                    self.waker_cache.remove(&task_id);
+            // Tasks of throttled servers wait for the budget's replenishment.
+            match server.and_then(|server| self.servers[server.0].throttled_until) {
+                Some(until) => {
+                    self.throttled.insert(task_id, until);
                 }
-                Poll::Pending => {
-                    let now = self.timer.now();
-                    if task.deadline <= now.into() {
-                        match &task.behavior {
-                            DelayStrategy::ReturnError => {
-                                return Err(ExecutorError::MissedDeadline(task_id.0))
-                            }
-                            DelayStrategy::Panic => panic!(
+                None => break task_id,
+            }
+        };
+        self.poll_task(task_id)
+            .map_err(|error| self.remove_failed(error))?;
+        self.execute_console_commands(task_id);
+        Ok(true)
+    }
+
+    fn remove_failed(&mut self, error: ExecutorError) -> ExecutorError {
+        if let ExecutorError::RestartsExhausted(_) = error {
+            self.enter_safe_state();
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            if matches!(
+                error,
+                ExecutorError::MissedDeadline(_)
+                    | ExecutorError::ConstraintViolated(_)
+                    | ExecutorError::RestartsExhausted(_)
+                    | ExecutorError::SupervisionFailed(_)
+            ) {
+                watchdog.fault();
+            }
+        }
+        if let Some(task) = self.remove_task(error.task_id()) {
+            self.failed += 1;
+            task.fail(&error);
+            self.failed_task = Some(task.metadata());
+        }
+        self.leave_high_criticality_mode_if_idle();
+        error
+    }
+
+    /// Removes the task along with everything the executor tracks about it.
+    fn remove_task(&mut self, task_id: TaskId) -> Option<Task> {
+        self.waker_cache.remove(&task_id);
+        self.task_queue.remove(task_id);
+        self.releases.remove(&task_id);
+        self.throttled.remove(&task_id);
+        self.suspended.remove(&task_id);
+        self.tasks.remove(&task_id)
+    }
+
+    fn poll_task(&mut self, task_id: TaskId) -> Result<(), ExecutorError> {
+        let task = match self.tasks.get_mut(&task_id) {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        let waker = self
+            .waker_cache
+            .entry(task_id)
+            .or_insert_with(|| TaskWaker::new(task_id, self.task_queue.clone()));
+        let mut context = Context::from_waker(waker);
+
+        let start = self.timer.now();
+        task.polls += 1;
+        self.polls += 1;
+        if let Some((console, shared)) = &self.console {
+            if shared.is_tracing() && *console != task_id {
+                shared.reply(format!(
+                    "Polling {} ({}) at {}.",
+                    task_id.0,
+                    task.name.unwrap_or("-"),
+                    self.timer.now()
+                ));
+            }
+        }
+        let poll = match poll_isolated(task, &mut context) {
+            Ok(poll) => poll,
+            Err(error) => {
+                // A caught panic only ends the task. Its handle and
+                // `failed_task` report it, and the other tasks keep running.
+                self.remove_failed(error);
+                return Ok(());
+            }
+        };
+        let elapsed = self.timer.elapsed_since(start);
+        task.executed += elapsed;
+        if let (Some(watchdog), Some(name)) = (&mut self.watchdog, task.name) {
+            if task.locals.checked_in.take() {
+                watchdog.checked_in(name, self.timer.now());
+            }
+        }
+        let (name, checkpoints) = (task.name, task.locals.checkpoints.take());
+        if let Some(server) = task.server {
+            self.servers[server.0].consume(elapsed);
+        }
+        if let Some(name) = name {
+            self.reach_checkpoints(task_id, name, checkpoints)?;
+        }
+        self.check_budget(task_id)?;
+
+        match poll {
+            Poll::Ready(()) => {
+                if self.job_failed(task_id) {
+                    self.restart(task_id)?;
+                    return Ok(());
+                }
+                // A job can finish late within a single long poll.
+                let now = self.timer.now();
+                let missed =
+                    matches!(self.tasks.get(&task_id), Some(task) if task.deadline < now.into());
+                self.record_job(task_id, missed)?;
+                self.schedule_publication(task_id);
+                if let (Some(task), Some(completed)) =
+                    (self.finish_job(task_id), &mut self.completed)
+                {
+                    completed.push(task.metadata());
+                }
+            }
+            Poll::Pending => {
+                let task = match self.tasks.get_mut(&task_id) {
+                    Some(task) => task,
+                    None => return Ok(()),
+                };
+                // In high-criticality mode, degraded tasks only run in the
+                // background and their deadlines don't matter.
+                let degraded =
+                    self.mode == CriticalityMode::High && task.criticality == Criticality::Low;
+                let now = self.timer.now();
+                if task.deadline <= now.into() && !degraded {
+                    if task.approximating {
+                        return Err(ExecutorError::MissedDeadline(task_id));
+                    }
+                    match &task.behavior {
+                        DelayStrategy::ReturnError => {
+                            return Err(ExecutorError::MissedDeadline(task_id))
+                        }
+                        DelayStrategy::Panic => {
+                            self.enter_safe_state();
+                            panic!(
                                 "We missed the deadline of a task with a DelayStrategy of panic."
-                            ),
-                            DelayStrategy::ContinueRunning => {
-                                //self.task_queue.push(task_id, now - task.deadline);
-                                // If the deadline is missed, we don't care how long the task runs anymore.
-                                self.task_queue.push(
-                                    task_id,
-                                    Deadline::Infinite.to_scheduling_timestamp(&self.timer),
-                                );
-                            }
-                            DelayStrategy::SilentlyAbort => {}
-                            DelayStrategy::InsteadApproximate(create_other_task) => {
-                                let other_task = create_other_task();
-                                self.spawn(other_task);
-                            }
+                            )
                         }
-                    } else {
-                        self.task_queue
-                            .push(task_id, task.deadline.to_scheduling_timestamp(&self.timer));
+                        DelayStrategy::ContinueRunning => {
+                            //self.task_queue.push(task_id, now - task.deadline);
+                            // If the deadline is missed, we don't care how long the task runs anymore.
+                            self.task_queue.push(
+                                task_id,
+                                Deadline::Infinite.to_scheduling_timestamp(&self.timer),
+                            );
+                        }
+                        DelayStrategy::SilentlyAbort => {
+                            self.finish_job(task_id);
+                        }
+                        DelayStrategy::InsteadApproximate(approximation) => {
+                            // This drops the original future.
+                            let (deadline, future) = approximation.start(now);
+                            task.future = future;
+                            task.deadline = deadline.into();
+                            task.executed = 0;
+                            task.approximating = true;
+                            self.task_queue.push(task_id, deadline);
+                        }
+                        DelayStrategy::MkFirm(_) => {
+                            self.record_job(task_id, true)?;
+                            self.finish_job(task_id);
+                        }
+                        DelayStrategy::Restart(_) => self.restart(task_id)?,
                     }
+                } else {
+                    let deadline = if degraded {
+                        Deadline::Infinite
+                    } else {
+                        scheduling_deadline(&self.servers, task)
+                    };
+                    self.task_queue
+                        .push(task_id, deadline.to_scheduling_timestamp(&self.timer));
                 }
             }
         }
@@ -117,36 +806,524 @@ impl<T: Timer> Executor<T> {
         Ok(())
     }
 
-    pub fn run(&mut self) -> Result<(), ExecutorError> {
-        while !self.task_queue.is_empty() {
-            self.run_ready_tasks()?;
+    /// Moves tasks whose release time has come into the task queue.
+    fn release_due_tasks(&mut self) -> Result<(), ExecutorError> {
+        let now = self.timer.now();
+        let due: Vec<TaskId> = self
+            .releases
+            .iter()
+            .filter(|(_, release)| **release <= now)
+            .map(|(task_id, _)| *task_id)
+            .collect();
+        for task_id in due {
+            self.releases.remove(&task_id);
+            if self.should_skip(task_id, now) {
+                self.record_job(task_id, true)?;
+                self.finish_job(task_id);
+                continue;
+            }
+            if let Some(task) = self.tasks.get(&task_id) {
+                task.ports.sample();
+                self.task_queue.push(
+                    task_id,
+                    scheduling_deadline(&self.servers, task).to_scheduling_timestamp(&self.timer),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the tasks of servers whose budget was replenished again.
+    fn unthrottle_tasks(&mut self, now: Timestamp) {
+        let due: Vec<TaskId> = self
+            .throttled
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(task_id, _)| *task_id)
+            .collect();
+        for task_id in due {
+            self.throttled.remove(&task_id);
+            self.reschedule(task_id);
+        }
+    }
+
+    /// Remembers to publish the outputs of a finished job at its deadline.
+    fn schedule_publication(&mut self, task_id: TaskId) {
+        let task = match self.tasks.get(&task_id) {
+            Some(task) if task.ports.has_outputs() => task,
+            _ => return,
+        };
+        let now = self.timer.now();
+        let at = match task.deadline {
+            Deadline::Finite(deadline) => deadline.max(now),
+            Deadline::Infinite => now,
+        };
+        self.publications.push((at, task.ports.publication()));
+    }
+
+    fn publish_due_outputs(&mut self, now: Timestamp) {
+        let (due, pending) = self
+            .publications
+            .drain(..)
+            .partition::<Vec<_>, _>(|(at, _)| *at <= now);
+        self.publications = pending;
+        for (_, publication) in due {
+            publication.publish();
+        }
+    }
+
+    /// Moves tasks forward in the queue when they inherit an earlier deadline
+    /// from a task that waits for them.
+    fn inherit_deadlines(&mut self) {
+        for (task_id, task) in self.tasks.iter_mut() {
+            if self.releases.contains_key(task_id) {
+                continue;
+            }
+            let inherited = task.inherited_deadline().map(|(deadline, _)| deadline);
+            if inherited == task.inherited {
+                continue;
+            }
+            task.inherited = inherited;
+            if let Some(deadline) = inherited {
+                self.task_queue
+                    .push(*task_id, deadline.to_scheduling_timestamp(&self.timer));
+            }
+        }
+    }
+
+    /// Adds a constant bandwidth server whose tasks may run for `budget` every
+    /// `period`. Tasks are added to it using [`Task::in_server`].
+    pub fn add_server(&mut self, budget: Timestamp, period: Timestamp) -> ServerId {
+        self.servers.push(Server::new(budget, period));
+        ServerId(self.servers.len() - 1)
+    }
+
+    /// Kicks the watchdog while the system is healthy, i.e. all critical
+    /// tasks check in within their period and no deadline miss is returned as
+    /// an error. Critical tasks are watched from now on.
+    ///
+    /// A returned deadline miss stops the kicks until
+    /// [`clear_watchdog_fault`](Self::clear_watchdog_fault) is called.
+    pub fn set_watchdog(&mut self, watchdog: impl Watchdog + 'static, config: WatchdogConfig) {
+        self.watchdog = Some(WatchdogState::new(
+            Box::new(watchdog),
+            config,
+            self.timer.now(),
+        ));
+    }
+
+    /// Resumes kicking the watchdog after a deadline miss was returned, e.g.
+    /// once the application recovered from it.
+    pub fn clear_watchdog_fault(&mut self) {
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.clear_fault();
+        }
+    }
+
+    /// Enters the safe state when a task's deadline miss or violation makes
+    /// the executor panic, or when a task exhausts its restarts.
+    pub fn set_safe_state(&mut self, safe_state: SafeState) {
+        self.safe_state = Some(safe_state);
+    }
+
+    fn enter_safe_state(&self) {
+        if let Some(safe_state) = &self.safe_state {
+            safe_state.enter(&self.timer);
+        }
+    }
+
+    /// Supervises the checkpoints of the task with the entity's name from now
+    /// on.
+    pub fn supervise(&mut self, mut entity: SupervisedEntity) {
+        entity.start(self.timer.now());
+        self.supervised.push(entity);
+    }
+
+    /// The most recent supervision violations, oldest first, along with the
+    /// names of the tasks. Only the last [`MAX_VIOLATIONS`] are kept.
+    pub fn violations(&self) -> &[(&'static str, Violation)] {
+        &self.violations
+    }
+
+    /// Removes and returns the recorded supervision violations.
+    pub fn take_violations(&mut self) -> Vec<(&'static str, Violation)> {
+        core::mem::take(&mut self.violations)
+    }
+
+    fn reach_checkpoints(
+        &mut self,
+        task_id: TaskId,
+        name: &'static str,
+        checkpoints: Vec<&'static str>,
+    ) -> Result<(), ExecutorError> {
+        let now = self.timer.now();
+        for index in 0..self.supervised.len() {
+            if self.supervised[index].task() != name {
+                continue;
+            }
+            let mut violations = Vec::new();
+            for checkpoint in &checkpoints {
+                self.supervised[index].reached(checkpoint, now, &mut violations);
+            }
+            self.escalate_violations(index, task_id, violations)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the entities whose task is running. The others start over once
+    /// their task runs again.
+    fn check_supervision(&mut self, now: Timestamp) -> Result<(), ExecutorError> {
+        for index in 0..self.supervised.len() {
+            let name = self.supervised[index].task();
+            let task_id = match self.tasks.values().find(|task| task.name == Some(name)) {
+                Some(task) => task.id,
+                None => {
+                    self.supervised[index].start(now);
+                    continue;
+                }
+            };
+            let mut violations = Vec::new();
+            self.supervised[index].check(now, &mut violations);
+            self.escalate_violations(index, task_id, violations)?;
         }
         Ok(())
     }
+
+    fn escalate_violations(
+        &mut self,
+        index: usize,
+        task_id: TaskId,
+        violations: Vec<Violation>,
+    ) -> Result<(), ExecutorError> {
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let entity = &self.supervised[index];
+        self.violations.extend(
+            violations
+                .into_iter()
+                .map(|violation| (entity.task(), violation)),
+        );
+        let excess = self.violations.len().saturating_sub(MAX_VIOLATIONS);
+        self.violations.drain(..excess);
+        match entity.escalation() {
+            Escalation::ReturnError => Err(ExecutorError::SupervisionFailed(task_id)),
+            Escalation::Panic => {
+                self.enter_safe_state();
+                panic!("A task violated its supervision.")
+            }
+            Escalation::Callback(callback) => {
+                callback(task_id);
+                Ok(())
+            }
+            Escalation::ModeChange => {
+                self.enter_high_criticality_mode();
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether a just released job of an (m,k)-firm task should be skipped
+    /// because it can't meet its deadline anyway and the constraint tolerates
+    /// another miss. Whether a job can meet its deadline is estimated using
+    /// the optimistic budgets of all tasks with earlier deadlines.
+    fn should_skip(&self, task_id: TaskId, now: Timestamp) -> bool {
+        let task = match self.tasks.get(&task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+        let can_afford_miss = match &task.behavior {
+            DelayStrategy::MkFirm(firm) => firm.can_afford_miss(),
+            _ => false,
+        };
+        let budget = match task.budget {
+            Some(budget) if can_afford_miss => budget,
+            _ => return false,
+        };
+        // Only active jobs compete with the released one. Tasks waiting for
+        // their next release don't.
+        let demand: Timestamp = self
+            .tasks
+            .iter()
+            .filter(|(id, other)| {
+                **id != task_id
+                    && !self.releases.contains_key(id)
+                    && other.deadline <= task.deadline
+            })
+            .filter_map(|(_, other)| {
+                other
+                    .budget
+                    .map(|it| it.optimistic.saturating_sub(other.executed))
+            })
+            .sum();
+        Deadline::Finite(now + demand + budget.optimistic) > task.deadline
+    }
+
+    /// Records the outcome of a job of an (m,k)-firm task and escalates if its
+    /// constraint is violated.
+    fn record_job(&mut self, task_id: TaskId, missed: bool) -> Result<(), ExecutorError> {
+        let firm = match self.tasks.get_mut(&task_id).map(|task| &mut task.behavior) {
+            Some(DelayStrategy::MkFirm(firm)) => firm,
+            _ => return Ok(()),
+        };
+        if !firm.record(missed) {
+            return Ok(());
+        }
+        match firm.escalation() {
+            Escalation::ReturnError => Err(ExecutorError::ConstraintViolated(task_id)),
+            Escalation::Panic => {
+                self.enter_safe_state();
+                panic!("A task violated its (m,k)-firm constraint.")
+            }
+            Escalation::Callback(callback) => {
+                callback(task_id);
+                Ok(())
+            }
+            Escalation::ModeChange => {
+                self.enter_high_criticality_mode();
+                Ok(())
+            }
+        }
+    }
+
+    fn job_failed(&self, task_id: TaskId) -> bool {
+        match self.tasks.get(&task_id).map(|task| &task.behavior) {
+            Some(DelayStrategy::Restart(restart)) => restart.take_failure(),
+            _ => false,
+        }
+    }
+
+    /// Replaces the current job of a task with a fresh one.
+    fn restart(&mut self, task_id: TaskId) -> Result<(), ExecutorError> {
+        let now = self.timer.now();
+        let task = match self.tasks.get_mut(&task_id) {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        let restart = match &mut task.behavior {
+            DelayStrategy::Restart(restart) => restart.restart(now),
+            _ => return Ok(()),
+        };
+        match restart {
+            Some((release, deadline, future)) => {
+                task.future = future;
+                task.deadline = deadline.into();
+                task.executed = 0;
+                self.releases.insert(task_id, release);
+                Ok(())
+            }
+            None => Err(ExecutorError::RestartsExhausted(task_id)),
+        }
+    }
+
+    /// Drops the current job of a task. Periodic tasks wait for the release of
+    /// their next job, all other tasks are removed and returned.
+    fn finish_job(&mut self, task_id: TaskId) -> Option<Task> {
+        self.throttled.remove(&task_id);
+        let task = self.tasks.get_mut(&task_id)?;
+        let next_job = task
+            .periodic
+            .as_mut()
+            .and_then(|periodic| Some((periodic.next_job()?, periodic.relative_deadline)));
+        match next_job {
+            Some(((release, future), relative_deadline)) => {
+                task.future = future;
+                task.deadline = (release + relative_deadline).into();
+                task.executed = 0;
+                task.approximating = false;
+                self.releases.insert(task_id, release);
+                self.leave_high_criticality_mode_if_idle();
+                None
+            }
+            None => {
+                let task = self.remove_task(task_id);
+                self.leave_high_criticality_mode_if_idle();
+                task
+            }
+        }
+    }
+
+    /// Once no high-criticality job is active anymore, the executor is idle
+    /// from a high-criticality perspective and returns to low-criticality mode.
+    fn leave_high_criticality_mode_if_idle(&mut self) {
+        let releases = &self.releases;
+        if !self.tasks.iter().any(|(task_id, task)| {
+            task.criticality == Criticality::High && !releases.contains_key(task_id)
+        }) {
+            self.mode = CriticalityMode::Low;
+        }
+    }
+
+    /// Reacts to a task exceeding its execution budget after it was polled.
+    fn check_budget(&mut self, task_id: TaskId) -> Result<(), ExecutorError> {
+        let task = match self.tasks.get(&task_id) {
+            Some(task) => task,
+            None => return Ok(()),
+        };
+        let budget = match task.budget {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+        if task.executed > budget.pessimistic {
+            return Err(ExecutorError::BudgetOverrun(task_id));
+        }
+        if task.criticality == Criticality::High
+            && self.mode == CriticalityMode::Low
+            && task.executed > budget.optimistic
+        {
+            self.enter_high_criticality_mode();
+        }
+        Ok(())
+    }
+
+    fn enter_high_criticality_mode(&mut self) {
+        self.mode = CriticalityMode::High;
+        if self.low_criticality_policy == LowCriticalityPolicy::Drop {
+            let dropped: Vec<TaskId> = self
+                .tasks
+                .values()
+                .filter(|task| task.criticality != Criticality::High)
+                .map(|task| task.id)
+                .collect();
+            for task_id in dropped {
+                self.remove_failed(ExecutorError::Cancelled(task_id));
+            }
+        }
+    }
+
+    /// Runs until all tasks are finished.
+    pub fn run(&mut self) -> Result<(), ExecutorError> {
+        while self.step(None)? {}
+        Ok(())
+    }
+
+    /// Runs until the given time or until all tasks are finished.
+    pub fn run_until(&mut self, until: Timestamp) -> Result<(), ExecutorError> {
+        while self.timer.now() < until && self.step(Some(until))? {}
+        Ok(())
+    }
+
+    /// Runs for the given duration or until all tasks are finished.
+    pub fn run_for(&mut self, duration: Duration) -> Result<(), ExecutorError> {
+        self.run_until(self.timer.now() + duration.as_secs())
+    }
+
+    /// Polls a task or, if none is ready, waits for the next release (but not
+    /// past `until`). Returns whether there are tasks left.
+    fn step(&mut self, until: Option<Timestamp>) -> Result<bool, ExecutorError> {
+        if self.poll_once()? {
+            return Ok(true);
+        }
+        let wakeup = match (self.next_wakeup(), until) {
+            (None, _) => return Ok(false),
+            (Some(wakeup), Some(until)) => wakeup.min(until),
+            (Some(wakeup), None) => wakeup,
+        };
+        let now = self.timer.now();
+        if wakeup > now {
+            self.timer.delay(Duration::from_secs(wakeup - now));
+        }
+        Ok(true)
+    }
+
+    /// When the executor has something to do next. This is now if a task is
+    /// queued, otherwise the earliest release, replenishment of a throttled
+    /// server, publication of outputs or kick of the watchdog, and `None` if
+    /// there is nothing left to do.
+    pub fn next_wakeup(&self) -> Option<Timestamp> {
+        if self.spawner.has_spawned() || !self.task_queue.is_empty() {
+            return Some(self.timer.now());
+        }
+        let wakeup = self
+            .releases
+            .values()
+            .chain(self.throttled.values())
+            .chain(self.publications.iter().map(|(at, _)| at))
+            .min()
+            .copied()?;
+        // The watchdog is only kicked while there's something left to do.
+        match self.watchdog.as_ref().and_then(WatchdogState::next_kick) {
+            Some(kick) => Some(wakeup.min(kick)),
+            None => Some(wakeup),
+        }
+    }
+}
+
+impl<T: Timer> LocalSpawn for Executor<T> {
+    fn spawn_local_obj(&self, future: LocalFutureObj<'static, ()>) -> Result<(), SpawnError> {
+        self.spawner.spawn_local_obj(future)
+    }
+}
+
+impl<T: Timer> Spawn for Executor<T> {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
+        self.spawner.spawn_obj(future)
+    }
 }
 
+/// Runs the future on a new executor until it completes.
+#[cfg(feature = "std")]
+pub fn block_on<F: Future + 'static>(
+    future: F,
+    deadline: Deadline,
+) -> Result<F::Output, ExecutorError> {
+    Executor::new().block_on(future, deadline)
+}
+
+/// Polls the task. If it panics, the panic is caught and turned into an
+/// error, unless the task propagates panics.
+#[cfg(feature = "std")]
+fn poll_isolated(task: &mut Task, context: &mut Context) -> Result<Poll<()>, ExecutorError> {
+    if !task.isolate_panics {
+        return Ok(task.poll(context));
+    }
+    super::safe_state::isolate(|| {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| task.poll(context)))
+    })
+    .map_err(|payload| {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast_ref::<&'static str>() {
+                Some(message) => String::from(*message),
+                None => String::from("Box<dyn Any>"),
+            },
+        };
+        ExecutorError::TaskPanicked(task.id, message)
+    })
+}
+
+#[cfg(not(feature = "std"))]
+fn poll_isolated(task: &mut Task, context: &mut Context) -> Result<Poll<()>, ExecutorError> {
+    Ok(task.poll(context))
+}
+
+/// The deadline a task is scheduled with. Tasks in a constant bandwidth
+/// server use the server's deadline.
+fn scheduling_deadline(servers: &[Server], task: &Task) -> Deadline {
+    match task.server {
+        Some(server) => servers[server.0].deadline.into(),
+        None => task.effective_deadline(),
+    }
+}
+
+/// Wakes a task with the priority the executor last queued it with, so it
+/// follows changes of the task's deadline.
 struct TaskWaker {
     task_id: TaskId,
-    deadline: Deadline,
     task_queue: Arc<PriorityQueue<TaskId, Timestamp>>,
 }
 
 impl TaskWaker {
     #[allow(clippy::new_ret_no_self)]
-    fn new(
-        task_id: TaskId,
-        deadline: Deadline,
-        task_queue: Arc<PriorityQueue<TaskId, Timestamp>>,
-    ) -> Waker {
+    fn new(task_id: TaskId, task_queue: Arc<PriorityQueue<TaskId, Timestamp>>) -> Waker {
         Waker::from(Arc::new(TaskWaker {
             task_id,
-            deadline,
             task_queue,
         }))
     }
 
     fn wake_task(&self) {
-        self.task_queue.push(self.task_id, 999);
+        self.task_queue.wake(self.task_id);
     }
 }
 
@@ -166,7 +1343,7 @@ trait DeadlineExt {
 impl DeadlineExt for Deadline {
     fn to_scheduling_timestamp<T: Timer>(&self, timer: &T) -> Timestamp {
         match self {
-            Deadline::Infinite => (timer.now() + 9999),
+            Deadline::Infinite => timer.now() + 9999,
             Deadline::Finite(it) => *it,
         }
     }
@@ -0,0 +1,74 @@
+use alloc::boxed::Box;
+
+use super::TaskId;
+
+/// An (m,k)-firm deadline constraint: at least `m` out of any `k` consecutive
+/// jobs of a task have to meet their deadline.
+///
+/// Jobs that miss their deadline are aborted. Only if the constraint itself is
+/// violated, the [`Escalation`] kicks in.
+pub struct MkFirm {
+    m: u32,
+    k: u32,
+    escalation: Escalation,
+    /// The outcomes of the last `k` jobs. A set bit means the job missed its
+    /// deadline, the least significant bit is the latest job.
+    misses: u64,
+}
+
+/// What to do when an (m,k)-firm constraint is violated.
+pub enum Escalation {
+    /// Makes the executor's `run` function return an error.
+    ReturnError,
+
+    /// Panics.
+    Panic,
+
+    /// Calls the function with the ID of the task and continues.
+    Callback(Box<dyn Fn(TaskId)>),
+
+    /// Switches the executor into high-criticality mode.
+    ModeChange,
+}
+
+impl MkFirm {
+    pub fn new(m: u32, k: u32, escalation: Escalation) -> Self {
+        assert!(m <= k, "m can't be larger than k.");
+        assert!(
+            (1..=64).contains(&k),
+            "Only windows of 1 to 64 jobs are supported."
+        );
+        Self {
+            m,
+            k,
+            escalation,
+            misses: 0,
+        }
+    }
+
+    pub(crate) fn escalation(&self) -> &Escalation {
+        &self.escalation
+    }
+
+    fn window_after(&self, missed: bool) -> u64 {
+        let mask = if self.k == 64 {
+            u64::MAX
+        } else {
+            (1 << self.k) - 1
+        };
+        ((self.misses << 1) | missed as u64) & mask
+    }
+
+    /// Whether the next job can miss its deadline without violating the
+    /// constraint.
+    pub(crate) fn can_afford_miss(&self) -> bool {
+        self.window_after(true).count_ones() <= self.k - self.m
+    }
+
+    /// Records the outcome of a job and returns whether the constraint is now
+    /// violated.
+    pub(crate) fn record(&mut self, missed: bool) -> bool {
+        self.misses = self.window_after(missed);
+        self.misses.count_ones() > self.k - self.m
+    }
+}
@@ -0,0 +1,306 @@
+//! Task graphs with an end-to-end deadline.
+//!
+//! A [`TaskGraph`] consists of stages that pass values along their edges.
+//! Each stage is released once all of its predecessors finished. The
+//! deadlines of the stages are derived from the end-to-end deadline of the
+//! graph: A stage has to finish early enough that all of its successors can
+//! still finish in time if they run for their worst-case execution time.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    future::Future,
+};
+
+use super::{
+    deadline::Deadline,
+    executor::ExecutorError,
+    join::Join,
+    spawner::{Spawner, WeakSpawner},
+    DelayStrategy, Task, TaskFuture,
+};
+use crate::time::Timestamp;
+
+/// The output of a stage, which can be used as the input of other stages.
+pub struct Node<T> {
+    index: usize,
+    output: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            output: self.output.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Node<T> {
+    /// The output of the stage, if it finished.
+    pub fn output(&self) -> Option<T> {
+        self.output.borrow().clone()
+    }
+}
+
+/// The inputs of a stage: `()`, a single [`Node`] or a tuple of them.
+pub trait Inputs: 'static {
+    type Values;
+
+    fn predecessors(&self) -> Vec<usize>;
+
+    /// The outputs of the predecessors. May only be called once all of them
+    /// finished.
+    fn values(&self) -> Self::Values;
+}
+
+impl Inputs for () {
+    type Values = ();
+
+    fn predecessors(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    fn values(&self) -> Self::Values {}
+}
+
+impl<A: Clone + 'static> Inputs for Node<A> {
+    type Values = A;
+
+    fn predecessors(&self) -> Vec<usize> {
+        alloc::vec![self.index]
+    }
+
+    fn values(&self) -> Self::Values {
+        self.output()
+            .expect("A stage was released before its inputs.")
+    }
+}
+
+macro_rules! impl_inputs {
+    ($($A:ident),+) => {
+        impl<$($A: Clone + 'static),+> Inputs for ($(Node<$A>,)+) {
+            type Values = ($($A,)+);
+
+            #[allow(non_snake_case)]
+            fn predecessors(&self) -> Vec<usize> {
+                let ($($A,)+) = self;
+                alloc::vec![$($A.index),+]
+            }
+
+            #[allow(non_snake_case)]
+            fn values(&self) -> Self::Values {
+                let ($($A,)+) = self;
+                ($($A.values(),)+)
+            }
+        }
+    };
+}
+
+impl_inputs!(A);
+impl_inputs!(A, B);
+impl_inputs!(A, B, C);
+impl_inputs!(A, B, C, D);
+
+struct Stage {
+    name: &'static str,
+    wcet: Timestamp,
+    predecessors: Vec<usize>,
+    create: Box<dyn FnOnce() -> TaskFuture>,
+}
+
+/// A directed acyclic graph of stages that has to finish within an
+/// end-to-end deadline.
+pub struct TaskGraph {
+    end_to_end_deadline: Timestamp,
+    stages: Vec<Stage>,
+}
+
+impl TaskGraph {
+    /// Creates a graph that has to finish within `end_to_end_deadline` after
+    /// it's spawned.
+    pub fn new(end_to_end_deadline: Timestamp) -> Self {
+        Self {
+            end_to_end_deadline,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Adds a stage that runs after the stages given as `inputs` and receives
+    /// their outputs. `wcet` is the stage's worst-case execution time.
+    pub fn node<I, F>(
+        &mut self,
+        name: &'static str,
+        wcet: Timestamp,
+        inputs: I,
+        create: impl FnOnce(I::Values) -> F + 'static,
+    ) -> Node<F::Output>
+    where
+        I: Inputs,
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let node = Node {
+            index: self.stages.len(),
+            output: Rc::new(RefCell::new(None)),
+        };
+        let output = node.output.clone();
+        self.stages.push(Stage {
+            name,
+            wcet,
+            predecessors: inputs.predecessors(),
+            create: Box::new(move || {
+                Box::pin(async move {
+                    let value = create(inputs.values()).await;
+                    *output.borrow_mut() = Some(value);
+                })
+            }),
+        });
+        node
+    }
+
+    /// Derives the deadlines of the stages and returns the graph's state
+    /// along with the stages that can be released right away.
+    pub(crate) fn start(self, now: Timestamp, spawner: Spawner) -> (GraphHandle, Vec<Task>) {
+        let end_to_end_deadline = now + self.end_to_end_deadline;
+        let count = self.stages.len();
+
+        let mut successors = alloc::vec![Vec::new(); count];
+        for (index, stage) in self.stages.iter().enumerate() {
+            for predecessor in &stage.predecessors {
+                successors[*predecessor].push(index);
+            }
+        }
+        // Stages can only depend on earlier stages, so going backwards visits
+        // all successors of a stage before the stage itself.
+        let mut remaining_work: Vec<Timestamp> = alloc::vec![0; count];
+        for index in (0..count).rev() {
+            remaining_work[index] = successors[index]
+                .iter()
+                .map(|successor| self.stages[*successor].wcet + remaining_work[*successor])
+                .max()
+                .unwrap_or(0);
+        }
+
+        let graph = Rc::new(Graph {
+            stages: self
+                .stages
+                .into_iter()
+                .zip(successors)
+                .zip(remaining_work)
+                .map(|((stage, successors), remaining_work)| RunningStage {
+                    name: stage.name,
+                    deadline: end_to_end_deadline.saturating_sub(remaining_work),
+                    waiting_for: Cell::new(stage.predecessors.len()),
+                    successors,
+                    create: Cell::new(Some(stage.create)),
+                })
+                .collect(),
+            spawner: spawner.downgrade(),
+            finished: Cell::new(0),
+            failure: RefCell::new(None),
+        });
+        let roots = (0..count)
+            .filter(|index| graph.stages[*index].waiting_for.get() == 0)
+            .map(|index| graph.task(index))
+            .collect();
+        (GraphHandle { graph }, roots)
+    }
+}
+
+struct RunningStage {
+    name: &'static str,
+    deadline: Timestamp,
+    waiting_for: Cell<usize>,
+    successors: Vec<usize>,
+    create: Cell<Option<Box<dyn FnOnce() -> TaskFuture>>>,
+}
+
+struct Graph {
+    stages: Vec<RunningStage>,
+    /// Released stages own the graph, so it must not own them through the
+    /// spawner.
+    spawner: WeakSpawner,
+    finished: Cell<usize>,
+    failure: RefCell<Option<(&'static str, ExecutorError)>>,
+}
+
+impl Graph {
+    /// Creates the task of a released stage.
+    fn task(self: &Rc<Self>, index: usize) -> Task {
+        let stage = &self.stages[index];
+        let create = stage.create.take().expect("A stage was released twice.");
+        let graph = self.clone();
+        let mut task = Task::new(
+            stage.deadline.into(),
+            DelayStrategy::ReturnError,
+            async move {
+                create().await;
+                graph.finish(index);
+            },
+        )
+        .with_name(stage.name);
+        task.join = Some(Rc::new(StageJoin {
+            graph: self.clone(),
+            index,
+        }));
+        task
+    }
+
+    /// Releases the successors whose predecessors all finished.
+    fn finish(self: &Rc<Self>, index: usize) {
+        self.finished.set(self.finished.get() + 1);
+        let Some(spawner) = self.spawner.upgrade() else {
+            return;
+        };
+        for successor in &self.stages[index].successors {
+            let waiting_for = &self.stages[*successor].waiting_for;
+            waiting_for.set(waiting_for.get() - 1);
+            if waiting_for.get() == 0 {
+                spawner.spawn_task(self.task(*successor));
+            }
+        }
+    }
+}
+
+/// Records the first stage that failed.
+struct StageJoin {
+    graph: Rc<Graph>,
+    index: usize,
+}
+
+impl Join for StageJoin {
+    fn fail(&self, error: ExecutorError) {
+        let mut failure = self.graph.failure.borrow_mut();
+        if failure.is_none() {
+            *failure = Some((self.graph.stages[self.index].name, error));
+        }
+    }
+
+    fn inherited(&self) -> Option<(Deadline, u64)> {
+        None
+    }
+}
+
+/// The state of a spawned [`TaskGraph`].
+pub struct GraphHandle {
+    graph: Rc<Graph>,
+}
+
+impl GraphHandle {
+    /// Whether all stages finished.
+    pub fn is_finished(&self) -> bool {
+        self.graph.finished.get() == self.graph.stages.len()
+    }
+
+    /// The stage that caused the graph to miss its end-to-end deadline, if
+    /// any, and what went wrong.
+    pub fn failed_stage(&self) -> Option<(&'static str, ExecutorError)> {
+        self.graph.failure.borrow().clone()
+    }
+
+    /// The deadline of a stage derived from the end-to-end deadline.
+    pub fn deadline_of<T>(&self, node: &Node<T>) -> Timestamp {
+        self.graph.stages[node.index].deadline
+    }
+}
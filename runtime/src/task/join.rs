@@ -0,0 +1,184 @@
+use alloc::rc::Rc;
+use core::{
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use super::{deadline::Deadline, executor::ExecutorError, local, TaskId};
+
+/// The outcome of a task, once it's known. Tasks that are dropped before they
+/// finish are cancelled.
+struct JoinState<T> {
+    /// The ID of the task, once it's spawned.
+    task_id: Cell<TaskId>,
+    outcome: RefCell<Option<Result<T, ExecutorError>>>,
+    waker: RefCell<Option<Waker>>,
+    /// The deadline of the task waiting for this one, along with the ID of
+    /// the task the deadline originally belongs to.
+    inherited: Cell<Option<(Deadline, u64)>>,
+}
+
+impl<T> JoinState<T> {
+    fn complete(&self, outcome: Result<T, ExecutorError>) {
+        *self.outcome.borrow_mut() = Some(outcome);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Lets the executor access the state of a joinable task independent of the
+/// task's output type.
+pub(crate) trait Join {
+    /// Reports why the task didn't finish.
+    fn fail(&self, error: ExecutorError);
+
+    /// The deadline the task inherited from the task waiting for it.
+    fn inherited(&self) -> Option<(Deadline, u64)>;
+
+    /// Tells the state which ID the task got when it was spawned.
+    fn assign(&self, _task_id: TaskId) {}
+}
+
+impl<T> Join for JoinState<T> {
+    fn fail(&self, error: ExecutorError) {
+        if !matches!(*self.outcome.borrow(), Some(Ok(_))) {
+            self.complete(Err(error));
+        }
+    }
+
+    fn inherited(&self) -> Option<(Deadline, u64)> {
+        self.inherited.get()
+    }
+
+    fn assign(&self, task_id: TaskId) {
+        self.task_id.set(task_id);
+    }
+}
+
+/// Awaits the outcome of a task created with [`Task::joinable`](super::Task::joinable).
+///
+/// While a task waits for the handle, the joined task inherits the waiting
+/// task's deadline if it's earlier than its own. This also works
+/// transitively, so all tasks in a chain run with the tightest deadline.
+pub struct JoinHandle<T> {
+    state: Rc<JoinState<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Whether the task finished, failed or was cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.state.outcome.borrow().is_some()
+    }
+
+    /// Takes the outcome of the task if it's known.
+    pub fn try_join(&self) -> Option<Result<T, ExecutorError>> {
+        self.state.outcome.borrow_mut().take()
+    }
+
+    /// The outcome of the task. Tasks that are still running count as
+    /// cancelled.
+    pub fn into_outcome(self) -> Result<T, ExecutorError> {
+        self.try_join()
+            .unwrap_or(Err(ExecutorError::Cancelled(self.state.task_id.get())))
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, ExecutorError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.try_join() {
+            Some(outcome) => {
+                self.state.inherited.set(None);
+                Poll::Ready(outcome)
+            }
+            None => {
+                *self.state.waker.borrow_mut() = Some(context.waker().clone());
+                self.state.inherited.set(local::effective_deadline());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        self.state.inherited.set(None);
+    }
+}
+
+/// Stores the output of a task in its [`JoinHandle`]. If the task is dropped
+/// before it finished, it was cancelled.
+pub(crate) struct Completion<T> {
+    state: Rc<JoinState<T>>,
+}
+
+impl<T: 'static> Completion<T> {
+    /// Creates the completion of a task along with its handle and a way to
+    /// report failures.
+    pub fn new() -> (Self, JoinHandle<T>, Rc<dyn Join>) {
+        let state = Rc::new(JoinState {
+            task_id: Cell::new(TaskId::UNASSIGNED),
+            outcome: RefCell::new(None),
+            waker: RefCell::new(None),
+            inherited: Cell::new(None),
+        });
+        let handle = JoinHandle {
+            state: state.clone(),
+        };
+        let join = state.clone() as Rc<dyn Join>;
+        (Self { state }, handle, join)
+    }
+
+    pub fn complete(self, output: T) {
+        self.state.complete(Ok(output));
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        if self.state.outcome.borrow().is_none() {
+            self.state
+                .complete(Err(ExecutorError::Cancelled(self.state.task_id.get())));
+        }
+    }
+}
+
+/// Collects the outcomes of a tuple of [`JoinHandle`]s. Used by the
+/// [`spawn!`](crate::spawn) macro.
+#[doc(hidden)]
+pub trait JoinAll {
+    type Output;
+
+    fn join_all(self) -> Self::Output;
+}
+
+macro_rules! impl_join_all {
+    ($($T:ident),+) => {
+        impl<$($T),+> JoinAll for ($(JoinHandle<$T>,)+) {
+            type Output = ($(Result<$T, ExecutorError>,)+);
+
+            #[allow(non_snake_case)]
+            fn join_all(self) -> Self::Output {
+                let ($($T,)+) = self;
+                ($($T.into_outcome(),)+)
+            }
+        }
+    };
+}
+
+impl_join_all!(A);
+impl_join_all!(A, B);
+impl_join_all!(A, B, C);
+impl_join_all!(A, B, C, D);
+impl_join_all!(A, B, C, D, E);
+impl_join_all!(A, B, C, D, E, F);
+impl_join_all!(A, B, C, D, E, F, G);
+impl_join_all!(A, B, C, D, E, F, G, H);
+impl_join_all!(A, B, C, D, E, F, G, H, I);
+impl_join_all!(A, B, C, D, E, F, G, H, I, J);
+impl_join_all!(A, B, C, D, E, F, G, H, I, J, K);
+impl_join_all!(A, B, C, D, E, F, G, H, I, J, K, L);
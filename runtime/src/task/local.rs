@@ -0,0 +1,197 @@
+//! Task-local storage.
+//!
+//! Task-local variables are declared using the [`task_local!`](crate::task_local)
+//! macro. Each task lazily gets its own copy of the variable when it first
+//! accesses it, and the copy lives as long as the task (including all jobs of
+//! periodic tasks).
+
+use alloc::{collections::BTreeMap, rc::Rc, vec::Vec};
+use core::{
+    any::Any,
+    cell::{Cell, RefCell},
+    ptr,
+};
+
+use super::deadline::Deadline;
+
+/// Declares task-local variables, e.g.
+/// `task_local! { static REQUEST_ID: Cell<u32> = Cell::new(0); }`.
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr; $($rest:tt)*) => {
+        $crate::task_local!($(#[$attr])* $vis static $name: $ty = $init);
+        $crate::task_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::local::LocalKey<$ty> = {
+            fn init() -> $ty {
+                $init
+            }
+            $crate::task::local::LocalKey::new(init)
+        };
+    };
+}
+
+/// A key for a task-local variable, created by [`task_local!`](crate::task_local).
+pub struct LocalKey<T: 'static> {
+    init: fn() -> T,
+}
+
+/// A task-local variable was accessed outside of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessError;
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(init: fn() -> T) -> Self {
+        Self { init }
+    }
+
+    /// Calls the function with the current task's value of this variable.
+    ///
+    /// Panics if called outside of a task.
+    pub fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+        self.try_with(f)
+            .expect("Task-local variables can only be accessed from within a task.")
+    }
+
+    /// Calls the function with the current task's value of this variable, or
+    /// returns an error if called outside of a task.
+    pub fn try_with<R>(&'static self, f: impl FnOnce(&T) -> R) -> Result<R, AccessError> {
+        let value = with_current(|locals| {
+            let key = self as *const Self as usize;
+            locals
+                .values
+                .borrow_mut()
+                .entry(key)
+                .or_insert_with(|| Rc::new((self.init)()))
+                .clone()
+        })?;
+        // The map isn't borrowed anymore, so `f` can access other variables.
+        Ok(f(value.downcast_ref::<T>().unwrap()))
+    }
+}
+
+/// The deadline of the task that is currently running. If the task inherited
+/// an earlier deadline from a task waiting for it, that one is returned.
+pub fn deadline() -> Option<Deadline> {
+    with_current(|locals| locals.deadline.get()).ok()
+}
+
+/// The deadline of the task that is currently running along with the ID of
+/// the task the deadline originally belongs to.
+pub(crate) fn effective_deadline() -> Option<(Deadline, u64)> {
+    with_current(|locals| (locals.deadline.get(), locals.origin.get())).ok()
+}
+
+/// Calls the function while the deadline of the current task appears to be
+/// at most the given one.
+pub(crate) fn with_deadline<R>(deadline: Deadline, f: impl FnOnce() -> R) -> R {
+    let previous = with_current(|locals| {
+        let previous = locals.deadline.get();
+        locals.deadline.set(previous.min(deadline));
+        previous
+    });
+    let result = f();
+    if let Ok(previous) = previous {
+        let _ = with_current(|locals| locals.deadline.set(previous));
+    }
+    result
+}
+
+pub(crate) fn check_in() {
+    let _ = with_current(|locals| locals.checked_in.set(true));
+}
+
+pub(crate) fn checkpoint(name: &'static str) {
+    let _ = with_current(|locals| locals.checkpoints.borrow_mut().push(name));
+}
+
+/// The storage of a single task.
+pub(crate) struct TaskLocals {
+    values: RefCell<BTreeMap<usize, Rc<dyn Any>>>,
+    pub deadline: Cell<Deadline>,
+    /// The ID of the task the deadline originally belongs to.
+    pub origin: Cell<u64>,
+    /// Whether the task checked in with the watchdog since it was last polled.
+    pub checked_in: Cell<bool>,
+    /// The checkpoints the task reached since it was last polled.
+    pub checkpoints: RefCell<Vec<&'static str>>,
+}
+
+impl TaskLocals {
+    pub fn new(deadline: Deadline) -> Rc<Self> {
+        Rc::new(Self {
+            values: RefCell::new(BTreeMap::new()),
+            deadline: Cell::new(deadline),
+            origin: Cell::new(0),
+            checked_in: Cell::new(false),
+            checkpoints: RefCell::new(Vec::new()),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static CURRENT: Cell<*const TaskLocals> = const { Cell::new(ptr::null()) };
+}
+
+#[cfg(feature = "std")]
+fn current() -> *const TaskLocals {
+    CURRENT.with(|current| current.get())
+}
+
+#[cfg(feature = "std")]
+fn set_current(locals: *const TaskLocals) -> *const TaskLocals {
+    CURRENT.with(|current| current.replace(locals))
+}
+
+// Without std, a global is used. See the crate documentation for when that's
+// sound.
+#[cfg(not(feature = "std"))]
+static CURRENT: core::sync::atomic::AtomicPtr<TaskLocals> =
+    core::sync::atomic::AtomicPtr::new(ptr::null_mut());
+
+#[cfg(not(feature = "std"))]
+fn current() -> *const TaskLocals {
+    CURRENT.load(core::sync::atomic::Ordering::Acquire)
+}
+
+#[cfg(not(feature = "std"))]
+fn set_current(locals: *const TaskLocals) -> *const TaskLocals {
+    let previous = current();
+    CURRENT.store(
+        locals as *mut TaskLocals,
+        core::sync::atomic::Ordering::Release,
+    );
+    previous
+}
+
+fn with_current<R>(f: impl FnOnce(&TaskLocals) -> R) -> Result<R, AccessError> {
+    // SAFETY: The pointer is only set while the executor polls a task, which
+    // keeps the task's storage alive.
+    match unsafe { current().as_ref() } {
+        Some(locals) => Ok(f(locals)),
+        None => Err(AccessError),
+    }
+}
+
+/// Makes the given storage the current one until the returned guard is
+/// dropped.
+pub(crate) fn enter(locals: &Rc<TaskLocals>) -> Entered {
+    Entered {
+        previous: set_current(Rc::as_ptr(locals)),
+    }
+}
+
+pub(crate) struct Entered {
+    previous: *const TaskLocals,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        set_current(self.previous);
+    }
+}
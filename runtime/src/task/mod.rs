@@ -1,37 +1,110 @@
-use alloc::boxed::Box;
-//use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
 use core::{
     future::Future,
     pin::Pin,
     task::{Context, Poll},
 };
 
+use self::approximate::Approximation;
+use self::criticality::{Criticality, ExecutionBudget};
 use self::deadline::Deadline;
+use self::executor::ExecutorError;
+use self::firm::MkFirm;
+use self::join::{Completion, Join, JoinHandle};
+use self::local::TaskLocals;
+use self::periodic::Periodic;
+use self::ports::{Input, Output, Ports};
+use self::restart::Restart;
+use self::server::ServerId;
+use crate::time::Timestamp;
 
+pub mod approximate;
+pub mod console;
+pub mod criticality;
+pub mod cyclic;
 pub mod deadline;
 pub mod executor;
+pub mod firm;
+pub mod graph;
+pub mod join;
+pub mod local;
 pub mod noop;
+pub mod partition;
+mod periodic;
+pub mod ports;
+pub mod restart;
+pub mod safe_state;
+pub mod scope;
+pub mod server;
 pub mod sleep;
+pub mod spawner;
+pub mod supervision;
+pub mod supervisor;
+pub mod watchdog;
 
+type TaskFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Identifies a task within its executor. IDs are assigned when a task is
+/// spawned, so tasks of different executors may have the same ID.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct TaskId(u64);
+pub struct TaskId(pub(crate) u64);
 
 impl TaskId {
-    fn new() -> Self {
-        //static NEXT_ID: AtomicU64 = AtomicU64::new(0);
-        //TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
-        static mut NEXT_ID: u64 = 0;
-        let id = unsafe { TaskId(NEXT_ID) };
-        unsafe { NEXT_ID += 1 };
-        id
+    /// The ID of tasks that weren't spawned yet.
+    const UNASSIGNED: TaskId = TaskId(0);
+
+    pub fn as_u64(self) -> u64 {
+        self.0
     }
 }
 
+/// Information about a task, e.g. for diagnosing errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskMetadata {
+    pub id: TaskId,
+    pub name: Option<&'static str>,
+    /// When the task was spawned.
+    pub created: Timestamp,
+    pub deadline: Deadline,
+    /// The name of the task's [`DelayStrategy`].
+    pub strategy: &'static str,
+    pub criticality: Criticality,
+    pub tags: Vec<(&'static str, &'static str)>,
+}
+
 pub struct Task {
     id: TaskId,
+    name: Option<&'static str>,
+    /// When the task was spawned.
+    created: Timestamp,
+    tags: Vec<(&'static str, &'static str)>,
     deadline: Deadline,
+    /// The deadline relative to when the task is spawned, if it was given
+    /// that way.
+    relative_deadline: Option<Timestamp>,
     behavior: DelayStrategy,
-    future: Pin<Box<dyn Future<Output = ()>>>,
+    criticality: Criticality,
+    budget: Option<ExecutionBudget>,
+    /// The constant bandwidth server the task runs in, if any.
+    server: Option<ServerId>,
+    /// How long this task has been polled so far.
+    executed: Timestamp,
+    /// How often this task has been polled so far.
+    polls: u64,
+    /// Whether the current job was replaced by an approximation.
+    approximating: bool,
+    periodic: Option<Periodic>,
+    /// The ports the task reads and writes with logical execution time.
+    ports: Ports,
+    locals: Rc<TaskLocals>,
+    /// The state shared with the task's [`JoinHandle`], if it has one.
+    join: Option<Rc<dyn Join>>,
+    /// The inherited deadline the task was last scheduled with.
+    inherited: Option<Deadline>,
+    /// Whether a panic only removes this task instead of unwinding through
+    /// the executor.
+    isolate_panics: bool,
+    future: TaskFuture,
 }
 pub enum DelayStrategy {
     /// Makes the executor's `run` function return an error.
@@ -45,29 +118,236 @@ pub enum DelayStrategy {
     ContinueRunning,
 
     /// Doesn't continue running the tasks after the deadline, but also doesn't
-    /// report an error.
+    /// report an error. The job is dropped, so periodic tasks continue with
+    /// their next job and other tasks are removed.
     SilentlyAbort,
 
-    /// Stops running the task and instead approximates a result using the other
-    /// future.
-    InsteadApproximate(Box<dyn Fn() -> Task>),
+    /// Stops running the task and instead approximates a result from the last
+    /// partial result of the task.
+    InsteadApproximate(Approximation),
+
+    /// Aborts jobs that miss their deadline and only escalates if fewer than
+    /// m out of the last k jobs met their deadline. Under overload, jobs that
+    /// would miss anyway are skipped as long as the constraint allows it.
+    MkFirm(MkFirm),
+
+    /// Drops the task and starts it again from scratch. Tasks with this
+    /// strategy are also restarted if they fail.
+    Restart(Restart),
+}
+
+impl DelayStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DelayStrategy::ReturnError => "ReturnError",
+            DelayStrategy::Panic => "Panic",
+            DelayStrategy::ContinueRunning => "ContinueRunning",
+            DelayStrategy::SilentlyAbort => "SilentlyAbort",
+            DelayStrategy::InsteadApproximate(_) => "InsteadApproximate",
+            DelayStrategy::MkFirm(_) => "MkFirm",
+            DelayStrategy::Restart(_) => "Restart",
+        }
+    }
 }
 
 impl Task {
+    /// Creates a task with an absolute deadline. See
+    /// [`with_relative_deadline`](Self::with_relative_deadline) for one that
+    /// starts when the task is spawned.
     pub fn new(
         deadline: Deadline,
         behavior: DelayStrategy,
         future: impl Future<Output = ()> + 'static,
     ) -> Task {
         Task {
-            id: TaskId::new(),
+            id: TaskId::UNASSIGNED,
+            name: None,
+            created: 0,
+            tags: Vec::new(),
             deadline,
+            relative_deadline: None,
             behavior,
+            criticality: Criticality::default(),
+            budget: None,
+            server: None,
+            executed: 0,
+            polls: 0,
+            approximating: false,
+            periodic: None,
+            ports: Ports::default(),
+            locals: TaskLocals::new(deadline),
+            join: None,
+            inherited: None,
+            isolate_panics: true,
             future: Box::pin(future),
         }
     }
 
+    /// Creates a task along with a handle to await its output, or the reason
+    /// why it didn't finish.
+    pub fn joinable<F>(
+        deadline: Deadline,
+        behavior: DelayStrategy,
+        future: F,
+    ) -> (Task, JoinHandle<F::Output>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let mut task = Task::new(deadline, behavior, async {});
+        let (completion, handle, join) = Completion::new();
+        task.future = Box::pin(async move { completion.complete(future.await) });
+        task.join = Some(join);
+        (task, handle)
+    }
+
+    /// Creates a task that releases a new job every `period`, starting when
+    /// it's spawned. Each job has to finish within `relative_deadline` after
+    /// its release.
+    pub fn periodic<F: Future<Output = ()> + 'static>(
+        period: Timestamp,
+        relative_deadline: Timestamp,
+        behavior: DelayStrategy,
+        create_job: impl Fn() -> F + 'static,
+    ) -> Task {
+        let create_job = move || Box::pin(create_job()) as TaskFuture;
+        let mut task = Task::new(Deadline::Infinite, behavior, async {});
+        task.future = create_job();
+        task.periodic = Some(Periodic {
+            period,
+            relative_deadline,
+            next_release: 0,
+            remaining_jobs: None,
+            create_job: Box::new(create_job),
+        });
+        task
+    }
+
+    /// Creates a task that is restarted if it misses its deadline or fails.
+    pub fn restarting(deadline: Deadline, restart: Restart) -> Task {
+        let future = restart.first_job();
+        let mut task = Task::new(deadline, DelayStrategy::Restart(restart), async {});
+        task.future = future;
+        task
+    }
+
+    /// Limits how many jobs a periodic task releases in total.
+    pub fn with_job_limit(mut self, jobs: u64) -> Task {
+        let periodic = self
+            .periodic
+            .as_mut()
+            .expect("Only periodic tasks have a job limit.");
+        periodic.remaining_jobs = Some(jobs.saturating_sub(1));
+        self
+    }
+
+    /// Replaces the deadline with one that is relative to when the task is
+    /// spawned.
+    pub fn with_relative_deadline(mut self, deadline: Timestamp) -> Task {
+        self.relative_deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_name(mut self, name: &'static str) -> Task {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Attaches a user-defined tag to the task's metadata.
+    pub fn with_tag(mut self, key: &'static str, value: &'static str) -> Task {
+        self.tags.push((key, value));
+        self
+    }
+
+    pub fn metadata(&self) -> TaskMetadata {
+        TaskMetadata {
+            id: self.id,
+            name: self.name,
+            created: self.created,
+            deadline: self.deadline,
+            strategy: self.behavior.name(),
+            criticality: self.criticality,
+            tags: self.tags.clone(),
+        }
+    }
+
+    pub fn with_criticality(mut self, criticality: Criticality) -> Task {
+        self.criticality = criticality;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: ExecutionBudget) -> Task {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Lets panics of the task unwind through the executor. By default, a
+    /// panicking task is only removed on `std`.
+    pub fn propagating_panics(mut self) -> Task {
+        self.isolate_panics = false;
+        self
+    }
+
+    /// Reports to the task's [`JoinHandle`] why the task didn't finish.
+    fn fail(&self, error: &ExecutorError) {
+        if let Some(join) = &self.join {
+            join.fail(error.clone());
+        }
+    }
+
+    /// The deadline inherited from a task waiting for this one, if it's
+    /// earlier than the task's own deadline. Deadlines that originally belong
+    /// to this task are ignored, so waiting in a cycle doesn't keep the
+    /// deadline of a previous job alive.
+    fn inherited_deadline(&self) -> Option<(Deadline, TaskId)> {
+        match self.join.as_ref()?.inherited()? {
+            (deadline, origin) if origin != self.id.0 && deadline < self.deadline => {
+                Some((deadline, TaskId(origin)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The deadline the task is scheduled with.
+    fn effective_deadline(&self) -> Deadline {
+        match self.inherited_deadline() {
+            Some((deadline, _)) => deadline,
+            None => self.deadline,
+        }
+    }
+
+    /// Runs the task in the given constant bandwidth server, which limits how
+    /// much time it gets and schedules it with the server's deadline.
+    pub fn in_server(mut self, server: ServerId) -> Task {
+        self.server = Some(server);
+        self
+    }
+
+    /// Makes each job of the task see the value the input's port had when
+    /// the job was released.
+    pub fn with_input<T: Clone + 'static>(mut self, input: &Input<T>) -> Task {
+        self.ports.add_input(input);
+        self
+    }
+
+    /// Publishes the value each job writes to the output at the job's
+    /// deadline.
+    pub fn with_output<T: Clone + 'static>(mut self, output: &Output<T>) -> Task {
+        self.ports.add_output(output);
+        self
+    }
+
     fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        let (deadline, origin) = self
+            .inherited_deadline()
+            .unwrap_or((self.deadline, self.id));
+        self.locals.deadline.set(deadline);
+        self.locals.origin.set(origin.0);
+        let _entered = local::enter(&self.locals);
         self.future.as_mut().poll(context)
     }
 }
@@ -0,0 +1,151 @@
+//! Time partitioning of task groups, similar to ARINC 653.
+//!
+//! Each partition has its own [`Executor`] and only runs during its windows,
+//! which start at fixed offsets within the major frame. A partition that
+//! overruns its window cuts into the following one, but the overrun is taken
+//! from the partition's own next windows and the schedule doesn't drift.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use super::executor::{Executor, ExecutorError};
+use crate::time::{Timer, Timestamp};
+
+/// Identifies a partition of a [`PartitionedExecutor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionId(usize);
+
+/// A partition ran longer than its window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowOverrun {
+    pub partition: PartitionId,
+    pub by: Timestamp,
+}
+
+struct Partition<T: Timer> {
+    name: &'static str,
+    executor: Executor<T>,
+    /// How much time the partition used beyond its windows.
+    debt: Timestamp,
+}
+
+struct Window {
+    partition: PartitionId,
+    duration: Timestamp,
+}
+
+pub struct PartitionedExecutor<T: Timer + Clone> {
+    timer: T,
+    partitions: Vec<Partition<T>>,
+    windows: Vec<Window>,
+    /// When the next major frame starts.
+    start: Timestamp,
+    overruns: Vec<WindowOverrun>,
+    errors: Vec<(PartitionId, ExecutorError)>,
+}
+
+impl<T: Timer + Clone> PartitionedExecutor<T> {
+    /// The first major frame starts now.
+    pub fn new(timer: T) -> Self {
+        Self {
+            start: timer.now(),
+            timer,
+            partitions: Vec::new(),
+            windows: Vec::new(),
+            overruns: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn add_partition(&mut self, name: &'static str) -> PartitionId {
+        self.partitions.push(Partition {
+            name,
+            executor: Executor::with_timer(self.timer.clone()),
+            debt: 0,
+        });
+        PartitionId(self.partitions.len() - 1)
+    }
+
+    /// Appends a window of the given length to the major frame.
+    pub fn add_window(&mut self, partition: PartitionId, duration: Timestamp) -> &mut Self {
+        self.windows.push(Window {
+            partition,
+            duration,
+        });
+        self
+    }
+
+    /// The executor of a partition, e.g. for spawning tasks.
+    pub fn partition(&mut self, partition: PartitionId) -> &mut Executor<T> {
+        &mut self.partitions[partition.0].executor
+    }
+
+    pub fn name(&self, partition: PartitionId) -> &'static str {
+        self.partitions[partition.0].name
+    }
+
+    /// All windows that were overrun so far.
+    pub fn overruns(&self) -> &[WindowOverrun] {
+        &self.overruns
+    }
+
+    /// The errors of the partitions' executors. An error only affects the
+    /// partition it happened in.
+    pub fn errors(&self) -> &[(PartitionId, ExecutorError)] {
+        &self.errors
+    }
+
+    pub fn run_major_frames(&mut self, count: u64) {
+        for _ in 0..count {
+            self.run_major_frame();
+        }
+    }
+
+    /// Runs each window of the major frame once. Major frames follow each
+    /// other without gaps.
+    pub fn run_major_frame(&mut self) {
+        let mut start = self.start;
+        for index in 0..self.windows.len() {
+            let end = start + self.windows[index].duration;
+            self.run_window(index, start, end);
+            start = end;
+        }
+        self.start = start;
+    }
+
+    fn run_window(&mut self, index: usize, start: Timestamp, end: Timestamp) {
+        let id = self.windows[index].partition;
+        let now = self.timer.now();
+        if now < start {
+            self.timer.delay(Duration::from_secs(start - now));
+        }
+        // The window may have started late because of the previous one.
+        let began = self.timer.now();
+        let partition = &mut self.partitions[id.0];
+        let paid = partition.debt.min(end - start);
+        partition.debt -= paid;
+        let until = end - paid;
+
+        loop {
+            match partition.executor.run_until(until) {
+                Ok(()) => break,
+                Err(error) => self.errors.push((id, error)),
+            }
+        }
+
+        // Only time the partition ran itself counts as its overrun.
+        let now = self.timer.now();
+        let limit = until.max(began);
+        if now > limit {
+            partition.debt += now - limit;
+            self.overruns.push(WindowOverrun {
+                partition: id,
+                by: now - limit,
+            });
+        } else if now < end {
+            // The rest of the window belongs to the partition, even if it has
+            // nothing to do.
+            self.timer.delay(Duration::from_secs(end - now));
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use alloc::boxed::Box;
+
+use super::TaskFuture;
+use crate::time::Timestamp;
+
+/// Releases a new job of a task every period.
+pub(crate) struct Periodic {
+    pub period: Timestamp,
+    /// The deadline of each job, relative to its release.
+    pub relative_deadline: Timestamp,
+    pub next_release: Timestamp,
+    /// How many more jobs are released after the current one. `None` means
+    /// the task is released forever.
+    pub remaining_jobs: Option<u64>,
+    pub create_job: Box<dyn Fn() -> TaskFuture>,
+}
+
+impl Periodic {
+    /// Creates the next job and returns its release time, or `None` if the
+    /// task has no jobs left.
+    pub fn next_job(&mut self) -> Option<(Timestamp, TaskFuture)> {
+        match &mut self.remaining_jobs {
+            Some(0) => return None,
+            Some(remaining) => *remaining -= 1,
+            None => {}
+        }
+        let release = self.next_release;
+        self.next_release += self.period;
+        Some((release, (self.create_job)()))
+    }
+}
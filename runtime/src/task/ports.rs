@@ -0,0 +1,164 @@
+//! Ports for the logical execution time (LET) model.
+//!
+//! Tasks with LET semantics don't access shared values directly. Instead,
+//! the executor copies the values of their [`Input`]s when a job is released
+//! and publishes the values written to their [`Output`]s at the job's
+//! deadline, no matter when the job actually ran. This makes the timing of
+//! the communication deterministic.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+
+/// A value that is shared between tasks.
+pub struct Port<T> {
+    value: Rc<RefCell<T>>,
+}
+
+impl<T> Clone for Port<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: Clone + 'static> Port<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Rc::new(RefCell::new(value)),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        self.value.borrow().clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.value.borrow_mut() = value;
+    }
+
+    /// A view of the port that is only updated when a job is released.
+    pub fn input(&self) -> Input<T> {
+        Input {
+            port: self.clone(),
+            sampled: Rc::new(RefCell::new(self.get())),
+        }
+    }
+
+    /// A buffer that is written to the port at the deadline of a job.
+    pub fn output(&self) -> Output<T> {
+        Output {
+            port: self.clone(),
+            buffered: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+/// The value of a [`Port`] at the release of the current job.
+pub struct Input<T> {
+    port: Port<T>,
+    sampled: Rc<RefCell<T>>,
+}
+
+impl<T> Clone for Input<T> {
+    fn clone(&self) -> Self {
+        Self {
+            port: self.port.clone(),
+            sampled: self.sampled.clone(),
+        }
+    }
+}
+
+impl<T: Clone + 'static> Input<T> {
+    pub fn get(&self) -> T {
+        self.sampled.borrow().clone()
+    }
+
+    fn sample(&self) {
+        *self.sampled.borrow_mut() = self.port.get();
+    }
+}
+
+/// A value that is written to a [`Port`] at the deadline of the current job.
+pub struct Output<T> {
+    port: Port<T>,
+    buffered: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> Clone for Output<T> {
+    fn clone(&self) -> Self {
+        Self {
+            port: self.port.clone(),
+            buffered: self.buffered.clone(),
+        }
+    }
+}
+
+impl<T: Clone + 'static> Output<T> {
+    pub fn set(&self, value: T) {
+        *self.buffered.borrow_mut() = Some(value);
+    }
+
+    /// Takes the value written by the current job, to be published later.
+    fn snapshot(&self) -> Publish {
+        let (port, value) = (self.port.clone(), self.buffered.take());
+        Box::new(move || {
+            if let Some(value) = value {
+                port.set(value);
+            }
+        })
+    }
+}
+
+/// Writes a value taken from an [`Output`] to its port.
+type Publish = Box<dyn FnOnce()>;
+
+/// The inputs and outputs of a task.
+#[derive(Default)]
+pub(crate) struct Ports {
+    inputs: Vec<Box<dyn Fn()>>,
+    outputs: Vec<Box<dyn Fn() -> Publish>>,
+}
+
+impl Ports {
+    pub fn add_input<T: Clone + 'static>(&mut self, input: &Input<T>) {
+        let input = input.clone();
+        self.inputs.push(Box::new(move || input.sample()));
+    }
+
+    pub fn add_output<T: Clone + 'static>(&mut self, output: &Output<T>) {
+        let output = output.clone();
+        self.outputs.push(Box::new(move || output.snapshot()));
+    }
+
+    pub fn has_outputs(&self) -> bool {
+        !self.outputs.is_empty()
+    }
+
+    /// Copies the values of all input ports.
+    pub fn sample(&self) {
+        for input in &self.inputs {
+            input();
+        }
+    }
+
+    /// The values of the outputs of the current job, taken when it finished.
+    pub fn publication(&self) -> Publication {
+        Publication {
+            outputs: self.outputs.iter().map(|output| output()).collect(),
+        }
+    }
+}
+
+/// Outputs of a finished job that are published at the job's deadline.
+pub(crate) struct Publication {
+    outputs: Vec<Publish>,
+}
+
+impl Publication {
+    pub fn publish(self) {
+        for output in self.outputs {
+            output();
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use alloc::{boxed::Box, rc::Rc};
+use core::{cell::Cell, future::Future};
+
+use super::TaskFuture;
+use crate::time::Timestamp;
+
+/// Restarts a task from scratch when it misses its deadline or fails.
+pub struct Restart {
+    create_job: Box<dyn Fn(u32) -> TaskFuture>,
+    failed: Rc<Cell<bool>>,
+    limit: u32,
+    delay: Timestamp,
+    relative_deadline: Timestamp,
+    restarts: u32,
+}
+
+/// The output of a restartable job. Jobs returning an [`Err`] have failed and
+/// get restarted.
+pub trait JobOutput {
+    fn is_failure(&self) -> bool;
+}
+
+impl JobOutput for () {
+    fn is_failure(&self) -> bool {
+        false
+    }
+}
+
+impl<E> JobOutput for Result<(), E> {
+    fn is_failure(&self) -> bool {
+        self.is_err()
+    }
+}
+
+impl Restart {
+    /// Restarts the task up to `limit` times. Each restarted job gets a fresh
+    /// deadline `relative_deadline` after its start. The function that creates
+    /// the jobs receives how often the task was restarted so far.
+    pub fn new<F>(
+        relative_deadline: Timestamp,
+        limit: u32,
+        create_job: impl Fn(u32) -> F + 'static,
+    ) -> Self
+    where
+        F: Future + 'static,
+        F::Output: JobOutput,
+    {
+        let failed = Rc::new(Cell::new(false));
+        let failed_clone = failed.clone();
+        let create_job = move |restarts| {
+            let job = create_job(restarts);
+            let failed = failed_clone.clone();
+            Box::pin(async move {
+                if job.await.is_failure() {
+                    failed.set(true);
+                }
+            }) as TaskFuture
+        };
+        Self {
+            create_job: Box::new(create_job),
+            failed,
+            limit,
+            delay: 0,
+            relative_deadline,
+            restarts: 0,
+        }
+    }
+
+    /// Waits for the given time before each restart.
+    pub fn with_delay(mut self, delay: Timestamp) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Creates the first job.
+    pub(crate) fn first_job(&self) -> TaskFuture {
+        (self.create_job)(0)
+    }
+
+    /// Whether the last job failed. Resets the failure.
+    pub(crate) fn take_failure(&self) -> bool {
+        self.failed.replace(false)
+    }
+
+    /// Creates the next job and returns its release time and deadline, or
+    /// `None` if the restart limit is reached.
+    pub(crate) fn restart(&mut self, now: Timestamp) -> Option<(Timestamp, Timestamp, TaskFuture)> {
+        if self.restarts >= self.limit {
+            return None;
+        }
+        self.restarts += 1;
+        let release = now + self.delay;
+        Some((
+            release,
+            release + self.relative_deadline,
+            (self.create_job)(self.restarts),
+        ))
+    }
+}
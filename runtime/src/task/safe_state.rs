@@ -0,0 +1,258 @@
+//! Bringing the device into a safe state on unrecoverable faults.
+//!
+//! A [`SafeState`] consists of handlers that e.g. switch off outputs or close
+//! valves. Each handler has a time budget, and the budgets together have to
+//! fit into a configured maximum. The handlers run once, in priority order,
+//! when the executor panics because of a missed deadline or violated
+//! constraint, or when a task exhausts its restarts. Other panics can enter
+//! the safe state through a panic hook on `std` or by calling
+//! [`enter_installed`] from a `#[panic_handler]`.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::cell::{Cell, RefCell};
+
+use crate::time::{Timer, Timestamp};
+
+struct Handler {
+    name: &'static str,
+    priority: u8,
+    budget: Timestamp,
+    handler: Box<dyn Fn()>,
+}
+
+/// The handlers of a safe state before they are verified.
+pub struct SafeStateRegistry {
+    maximum: Timestamp,
+    handlers: Vec<Handler>,
+}
+
+impl SafeStateRegistry {
+    /// All handlers together may take at most `maximum`.
+    pub fn new(maximum: Timestamp) -> Self {
+        Self {
+            maximum,
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Adds a handler that may take at most `budget`. Handlers with a higher
+    /// priority run first.
+    pub fn handler(
+        mut self,
+        name: &'static str,
+        priority: u8,
+        budget: Timestamp,
+        handler: impl Fn() + 'static,
+    ) -> Self {
+        self.handlers.push(Handler {
+            name,
+            priority,
+            budget,
+            handler: Box::new(handler),
+        });
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SafeStateError {
+    /// The budgets of the handlers add up to more than the maximum. If their
+    /// sum overflows, `budgets` is `Timestamp::MAX`.
+    BudgetsExceedMaximum {
+        budgets: Timestamp,
+        maximum: Timestamp,
+    },
+}
+
+/// How entering the safe state went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafeStateReport {
+    pub elapsed: Timestamp,
+    /// The handlers that ran longer than their budget and how long they took.
+    pub overruns: Vec<(&'static str, Timestamp)>,
+    pub exceeded_maximum: bool,
+}
+
+struct Inner {
+    maximum: Timestamp,
+    handlers: Vec<Handler>,
+    entered: Cell<bool>,
+    report: RefCell<Option<SafeStateReport>>,
+}
+
+/// Verified safe-state handlers. Clones share whether the safe state was
+/// already entered.
+#[derive(Clone)]
+pub struct SafeState {
+    inner: Rc<Inner>,
+}
+
+impl SafeState {
+    /// Verifies that the handlers' budgets fit into the maximum.
+    pub fn new(registry: SafeStateRegistry) -> Result<Self, SafeStateError> {
+        let budgets = registry
+            .handlers
+            .iter()
+            .try_fold(0, |sum: Timestamp, handler| sum.checked_add(handler.budget));
+        match budgets {
+            Some(budgets) if budgets <= registry.maximum => {}
+            _ => {
+                return Err(SafeStateError::BudgetsExceedMaximum {
+                    budgets: budgets.unwrap_or(Timestamp::MAX),
+                    maximum: registry.maximum,
+                })
+            }
+        }
+        let mut handlers = registry.handlers;
+        handlers.sort_by_key(|handler| core::cmp::Reverse(handler.priority));
+        Ok(Self {
+            inner: Rc::new(Inner {
+                maximum: registry.maximum,
+                handlers,
+                entered: Cell::new(false),
+                report: RefCell::new(None),
+            }),
+        })
+    }
+
+    /// Runs all handlers in priority order, even if some of them overrun
+    /// their budget. Returns `None` if the safe state was already entered.
+    pub fn enter(&self, timer: &impl Timer) -> Option<SafeStateReport> {
+        let inner = &self.inner;
+        if inner.entered.replace(true) {
+            return None;
+        }
+        let start = timer.now();
+        let mut overruns = Vec::new();
+        for handler in &inner.handlers {
+            let handler_start = timer.now();
+            (handler.handler)();
+            let elapsed = timer.elapsed_since(handler_start);
+            if elapsed > handler.budget {
+                overruns.push((handler.name, elapsed));
+            }
+        }
+        let elapsed = timer.elapsed_since(start);
+        let report = SafeStateReport {
+            elapsed,
+            overruns,
+            exceeded_maximum: elapsed > inner.maximum,
+        };
+        *inner.report.borrow_mut() = Some(report.clone());
+        Some(report)
+    }
+
+    /// The report of entering the safe state, if it was entered.
+    pub fn report(&self) -> Option<SafeStateReport> {
+        self.inner.report.borrow().clone()
+    }
+
+    /// Makes this the safe state that [`enter_installed`] enters. On `std`,
+    /// this only applies to the current thread.
+    pub fn install(&self) {
+        installed::set(self.clone());
+    }
+
+    /// Enters the safe state when the current thread panics, before the
+    /// previous panic hook runs. Panics that the executor catches in tasks are
+    /// recoverable and don't enter it.
+    #[cfg(feature = "std")]
+    pub fn install_panic_hook<T: Timer + Send + Sync + 'static>(&self, timer: T) {
+        self.install();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !installed::isolating() {
+                enter_installed(&timer);
+            }
+            previous(info);
+        }));
+    }
+}
+
+/// Enters the [installed](SafeState::install) safe state, e.g. from a panic
+/// handler. Returns `None` if none is installed or it was already entered.
+pub fn enter_installed(timer: &impl Timer) -> Option<SafeStateReport> {
+    installed::get().and_then(|safe_state| safe_state.enter(timer))
+}
+
+/// Runs `poll`, which catches the panics of a task, without entering the
+/// safe state from the panic hook.
+#[cfg(feature = "std")]
+pub(crate) fn isolate<R>(poll: impl FnOnce() -> R) -> R {
+    installed::isolate(poll)
+}
+
+#[cfg(feature = "std")]
+mod installed {
+    use core::cell::{Cell, RefCell};
+
+    use super::SafeState;
+
+    std::thread_local! {
+        static INSTALLED: RefCell<Option<SafeState>> = const { RefCell::new(None) };
+        static ISOLATING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub fn isolate<R>(poll: impl FnOnce() -> R) -> R {
+        let previous = ISOLATING.with(|isolating| isolating.replace(true));
+        let result = poll();
+        ISOLATING.with(|isolating| isolating.set(previous));
+        result
+    }
+
+    pub fn isolating() -> bool {
+        ISOLATING.try_with(Cell::get).unwrap_or(false)
+    }
+
+    pub fn set(safe_state: SafeState) {
+        INSTALLED.with(|installed| *installed.borrow_mut() = Some(safe_state));
+    }
+
+    pub fn get() -> Option<SafeState> {
+        INSTALLED
+            .try_with(|installed| installed.borrow().clone())
+            .ok()
+            .flatten()
+    }
+}
+
+// Without std, a global is used. See the crate documentation for when that's
+// sound.
+#[cfg(not(feature = "std"))]
+mod installed {
+    use alloc::rc::Rc;
+    use core::{
+        mem::ManuallyDrop,
+        ptr,
+        sync::atomic::{AtomicPtr, Ordering},
+    };
+
+    use super::{Inner, SafeState};
+
+    static INSTALLED: AtomicPtr<Inner> = AtomicPtr::new(ptr::null_mut());
+
+    pub fn set(safe_state: SafeState) {
+        let new = Rc::into_raw(safe_state.inner) as *mut Inner;
+        // Not every target can swap atomically, and nothing else replaces the
+        // pointer in between.
+        let previous = INSTALLED.load(Ordering::Acquire);
+        INSTALLED.store(new, Ordering::Release);
+        if !previous.is_null() {
+            // SAFETY: The pointer was created by `Rc::into_raw` above.
+            drop(unsafe { Rc::from_raw(previous) });
+        }
+    }
+
+    pub fn get() -> Option<SafeState> {
+        let current = INSTALLED.load(Ordering::Acquire);
+        if current.is_null() {
+            return None;
+        }
+        // SAFETY: The installed pointer keeps its reference alive, so this
+        // only creates another one.
+        let inner = ManuallyDrop::new(unsafe { Rc::from_raw(current) });
+        Some(SafeState {
+            inner: Rc::clone(&inner),
+        })
+    }
+}
@@ -0,0 +1,158 @@
+//! Structured concurrency: Child tasks that can't outlive their scope.
+//!
+//! A [`scope`] runs a body that can spawn children using its [`Scope`]
+//! handle. The scope only finishes once the body and all children finished.
+//! If its deadline passes, or a child fails in [`ScopeMode::FailFast`], all
+//! unfinished children are cancelled together.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::{deadline::Deadline, local};
+use crate::time::Timer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeMode {
+    /// Cancels all children as soon as one of them fails.
+    FailFast,
+    /// Lets all children run to completion, even if some of them fail.
+    CollectAll,
+}
+
+/// Why a child of a scope didn't produce a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeError<E> {
+    /// The child returned an error.
+    Failed(E),
+    /// Another child failed and the scope is [`ScopeMode::FailFast`].
+    Cancelled,
+    /// The child was still running when the scope's deadline passed.
+    MissedDeadline,
+}
+
+type Child<T, E> = Pin<Box<dyn Future<Output = Result<T, E>>>>;
+
+/// A handle for spawning children into a scope.
+pub struct Scope<T, E> {
+    spawned: Rc<RefCell<Vec<Child<T, E>>>>,
+}
+
+impl<T, E> Clone for Scope<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            spawned: self.spawned.clone(),
+        }
+    }
+}
+
+impl<T, E> Scope<T, E> {
+    pub fn spawn(&self, child: impl Future<Output = Result<T, E>> + 'static) {
+        self.spawned.borrow_mut().push(Box::pin(child));
+    }
+}
+
+/// Runs the body and all children it spawns until they finished or the
+/// deadline passed. Children see the scope's deadline if it's earlier than
+/// the task's one. Returns the outcomes of the children in the order they
+/// were spawned.
+pub fn scope<T, E, B, F>(
+    timer: impl Timer,
+    deadline: Deadline,
+    mode: ScopeMode,
+    body: B,
+) -> impl Future<Output = Vec<Result<T, ScopeError<E>>>>
+where
+    B: FnOnce(Scope<T, E>) -> F,
+    F: Future<Output = ()> + 'static,
+{
+    let scope = Scope {
+        spawned: Rc::new(RefCell::new(Vec::new())),
+    };
+    ScopeFuture {
+        timer,
+        deadline,
+        mode,
+        body: Some(Box::pin(body(scope.clone()))),
+        scope,
+        children: Vec::new(),
+        outcomes: Vec::new(),
+    }
+}
+
+struct ScopeFuture<T, E, Ti> {
+    timer: Ti,
+    deadline: Deadline,
+    mode: ScopeMode,
+    scope: Scope<T, E>,
+    body: Option<Pin<Box<dyn Future<Output = ()>>>>,
+    children: Vec<Option<Child<T, E>>>,
+    outcomes: Vec<Option<Result<T, ScopeError<E>>>>,
+}
+
+impl<T, E, Ti> Unpin for ScopeFuture<T, E, Ti> {}
+
+impl<T, E, Ti: Timer> ScopeFuture<T, E, Ti> {
+    /// Drops the body and all unfinished children, which get the given error.
+    fn finish(&mut self, error: impl Fn() -> ScopeError<E>) -> Vec<Result<T, ScopeError<E>>> {
+        self.body = None;
+        self.children.clear();
+        self.outcomes
+            .drain(..)
+            .map(|outcome| outcome.unwrap_or_else(|| Err(error())))
+            .collect()
+    }
+
+    /// Polls the body and all children once. Returns whether a child failed.
+    fn poll_all(&mut self, context: &mut Context) -> bool {
+        if let Some(body) = &mut self.body {
+            if body.as_mut().poll(context).is_ready() {
+                self.body = None;
+            }
+        }
+        let mut index = 0;
+        loop {
+            for child in self.scope.spawned.take() {
+                self.children.push(Some(child));
+                self.outcomes.push(None);
+            }
+            if index >= self.children.len() {
+                return false;
+            }
+            if let Some(child) = &mut self.children[index] {
+                if let Poll::Ready(outcome) = child.as_mut().poll(context) {
+                    self.children[index] = None;
+                    let failed = outcome.is_err();
+                    self.outcomes[index] = Some(outcome.map_err(ScopeError::Failed));
+                    if failed && self.mode == ScopeMode::FailFast {
+                        return true;
+                    }
+                }
+            }
+            index += 1;
+        }
+    }
+}
+
+impl<T, E, Ti: Timer> Future for ScopeFuture<T, E, Ti> {
+    type Output = Vec<Result<T, ScopeError<E>>>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let failed = local::with_deadline(this.deadline, || this.poll_all(context));
+        if failed {
+            return Poll::Ready(this.finish(|| ScopeError::Cancelled));
+        }
+        if this.body.is_none() && this.children.iter().all(Option::is_none) {
+            return Poll::Ready(this.finish(|| unreachable!("All children finished.")));
+        }
+        if this.deadline <= this.timer.now().into() {
+            return Poll::Ready(this.finish(|| ScopeError::MissedDeadline));
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,70 @@
+use crate::time::Timestamp;
+
+/// Identifies a constant bandwidth server of an executor.
+///
+/// It's only the server's index, so it's only meaningful to the executor
+/// that created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerId(pub(crate) usize);
+
+/// A constant bandwidth server: The tasks of the server may run for `budget`
+/// every `period` and are scheduled using the server's deadline. When the
+/// budget is exhausted, the tasks are throttled until the server's deadline,
+/// when the budget is replenished and the deadline is postponed by a period.
+pub(crate) struct Server {
+    budget: Timestamp,
+    period: Timestamp,
+    remaining: Timestamp,
+    pub deadline: Timestamp,
+    /// When the budget is replenished, if it's exhausted.
+    pub throttled_until: Option<Timestamp>,
+}
+
+impl Server {
+    pub fn new(budget: Timestamp, period: Timestamp) -> Self {
+        assert!(
+            0 < budget && budget <= period,
+            "The budget has to be positive and fit into the period."
+        );
+        Self {
+            budget,
+            period,
+            remaining: 0,
+            deadline: 0,
+            throttled_until: None,
+        }
+    }
+
+    /// Called when a task arrives while the server is idle. If the remaining
+    /// budget can't be used up until the current deadline without exceeding
+    /// the server's bandwidth, the server starts over with a fresh budget.
+    pub fn activate(&mut self, now: Timestamp) {
+        if self.throttled_until.is_some() {
+            return;
+        }
+        if self.remaining * self.period >= self.deadline.saturating_sub(now) * self.budget {
+            self.remaining = self.budget;
+            self.deadline = now + self.period;
+        }
+    }
+
+    /// Charges the time a task of this server ran.
+    pub fn consume(&mut self, executed: Timestamp) {
+        self.remaining = self.remaining.saturating_sub(executed);
+        if self.remaining == 0 && self.throttled_until.is_none() {
+            self.throttled_until = Some(self.deadline);
+        }
+    }
+
+    /// Replenishes the budget if the server was throttled long enough.
+    pub fn replenish(&mut self, now: Timestamp) {
+        match self.throttled_until {
+            Some(until) if until <= now => {
+                self.throttled_until = None;
+                self.remaining = self.budget;
+                self.deadline = until + self.period;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::cell::{Cell, RefCell};
+use futures_util::task::{FutureObj, LocalFutureObj, LocalSpawn, Spawn, SpawnError};
+
+use super::{deadline::Deadline, executor::ExecutorError, DelayStrategy, Task};
+use crate::time::Timestamp;
+
+/// The deadline and strategy of tasks that are spawned through the
+/// [`Spawn`] and [`LocalSpawn`] traits.
+#[derive(Clone, Copy)]
+pub struct SpawnPolicy {
+    /// The deadline relative to when the executor accepts the task. `None`
+    /// means the task has no deadline.
+    pub relative_deadline: Option<Timestamp>,
+    pub strategy: fn() -> DelayStrategy,
+}
+
+impl Default for SpawnPolicy {
+    fn default() -> Self {
+        Self {
+            relative_deadline: None,
+            strategy: || DelayStrategy::ContinueRunning,
+        }
+    }
+}
+
+/// A handle for spawning futures onto an executor, even from within tasks.
+///
+/// Spawned futures are picked up by the executor before its next scheduling
+/// decision and get deadlines according to its [`SpawnPolicy`].
+#[derive(Clone, Default)]
+pub struct Spawner {
+    spawned: Rc<RefCell<Vec<Task>>>,
+    policy: Rc<Cell<SpawnPolicy>>,
+    /// Whether the executor is shutting down and doesn't accept new tasks.
+    closed: Rc<Cell<bool>>,
+}
+
+impl Spawner {
+    pub(crate) fn set_policy(&self, policy: SpawnPolicy) {
+        self.policy.set(policy);
+    }
+
+    /// Spawns the task, or cancels it if the executor is shutting down.
+    pub(crate) fn spawn_task(&self, task: Task) {
+        if self.closed.get() {
+            task.fail(&ExecutorError::Cancelled(task.id));
+            return;
+        }
+        self.spawned.borrow_mut().push(task);
+    }
+
+    pub(crate) fn close(&self) {
+        self.closed.set(true);
+    }
+
+    pub(crate) fn take_spawned(&self) -> Vec<Task> {
+        self.spawned.take()
+    }
+
+    pub(crate) fn has_spawned(&self) -> bool {
+        !self.spawned.borrow().is_empty()
+    }
+
+    /// A reference that doesn't keep the spawned tasks alive, for holders
+    /// that are themselves owned by such a task.
+    pub(crate) fn downgrade(&self) -> WeakSpawner {
+        WeakSpawner {
+            spawned: Rc::downgrade(&self.spawned),
+            policy: Rc::downgrade(&self.policy),
+            closed: Rc::downgrade(&self.closed),
+        }
+    }
+}
+
+pub(crate) struct WeakSpawner {
+    spawned: Weak<RefCell<Vec<Task>>>,
+    policy: Weak<Cell<SpawnPolicy>>,
+    closed: Weak<Cell<bool>>,
+}
+
+impl WeakSpawner {
+    /// The spawner, unless the executor was dropped.
+    pub(crate) fn upgrade(&self) -> Option<Spawner> {
+        Some(Spawner {
+            spawned: self.spawned.upgrade()?,
+            policy: self.policy.upgrade()?,
+            closed: self.closed.upgrade()?,
+        })
+    }
+}
+
+impl LocalSpawn for Spawner {
+    fn spawn_local_obj(&self, future: LocalFutureObj<'static, ()>) -> Result<(), SpawnError> {
+        if self.closed.get() {
+            return Err(SpawnError::shutdown());
+        }
+        let policy = self.policy.get();
+        let task = Task::new(Deadline::Infinite, (policy.strategy)(), future);
+        self.spawn_task(match policy.relative_deadline {
+            Some(deadline) => task.with_relative_deadline(deadline),
+            None => task,
+        });
+        Ok(())
+    }
+
+    fn status_local(&self) -> Result<(), SpawnError> {
+        if self.closed.get() {
+            return Err(SpawnError::shutdown());
+        }
+        Ok(())
+    }
+}
+
+impl Spawn for Spawner {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
+        self.spawn_local_obj(future.into())
+    }
+
+    fn status(&self) -> Result<(), SpawnError> {
+        self.status_local()
+    }
+}
@@ -0,0 +1,231 @@
+//! Supervision of tasks through checkpoints, similar to the AUTOSAR watchdog
+//! manager.
+//!
+//! Tasks report reaching a [`checkpoint`]. For each [`SupervisedEntity`], the
+//! executor verifies that
+//! - checkpoints are reached often enough, but not too often, within each
+//!   cycle (alive supervision),
+//! - the time between two checkpoints is within bounds (deadline
+//!   supervision), and
+//! - checkpoints are reached in an allowed order (logical supervision).
+//!
+//! Checkpoints count as reached when the poll that reached them ends. An
+//! entity is only checked while a task with its name is running, and
+//! violations are escalated for that task according to the entity's
+//! [`Escalation`].
+
+use alloc::vec::Vec;
+
+use super::{firm::Escalation, local};
+use crate::time::Timestamp;
+
+/// Reports that the current task reached the checkpoint with the given name.
+pub fn checkpoint(name: &'static str) {
+    local::checkpoint(name);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The checkpoint was reached the given number of times within a cycle.
+    Alive {
+        checkpoint: &'static str,
+        count: u32,
+    },
+    /// The time between the two checkpoints was out of bounds.
+    Deadline {
+        from: &'static str,
+        to: &'static str,
+        elapsed: Timestamp,
+    },
+    /// The checkpoint isn't allowed after the previous one.
+    Logical {
+        previous: Option<&'static str>,
+        checkpoint: &'static str,
+    },
+}
+
+struct Alive {
+    checkpoint: &'static str,
+    cycle: Timestamp,
+    min: u32,
+    max: u32,
+    count: u32,
+    cycle_start: Timestamp,
+}
+
+struct DeadlineSupervision {
+    from: &'static str,
+    to: &'static str,
+    min: Timestamp,
+    max: Timestamp,
+    started: Option<Timestamp>,
+}
+
+/// The supervision of the task with the given name.
+pub struct SupervisedEntity {
+    task: &'static str,
+    escalation: Escalation,
+    alive: Vec<Alive>,
+    deadlines: Vec<DeadlineSupervision>,
+    initial: Vec<&'static str>,
+    transitions: Vec<(&'static str, &'static str)>,
+    previous: Option<&'static str>,
+}
+
+impl SupervisedEntity {
+    pub fn new(task: &'static str, escalation: Escalation) -> Self {
+        Self {
+            task,
+            escalation,
+            alive: Vec::new(),
+            deadlines: Vec::new(),
+            initial: Vec::new(),
+            transitions: Vec::new(),
+            previous: None,
+        }
+    }
+
+    /// Requires the checkpoint to be reached `min` to `max` times per cycle.
+    pub fn alive(mut self, checkpoint: &'static str, cycle: Timestamp, min: u32, max: u32) -> Self {
+        assert!(cycle > 0, "The cycle has to be positive.");
+        self.alive.push(Alive {
+            checkpoint,
+            cycle,
+            min,
+            max,
+            count: 0,
+            cycle_start: 0,
+        });
+        self
+    }
+
+    /// Requires `to` to be reached `min` to `max` after `from`.
+    pub fn deadline(
+        mut self,
+        from: &'static str,
+        to: &'static str,
+        min: Timestamp,
+        max: Timestamp,
+    ) -> Self {
+        self.deadlines.push(DeadlineSupervision {
+            from,
+            to,
+            min,
+            max,
+            started: None,
+        });
+        self
+    }
+
+    /// Allows a sequence of checkpoints to start with the given one.
+    pub fn initial(mut self, checkpoint: &'static str) -> Self {
+        self.initial.push(checkpoint);
+        self
+    }
+
+    /// Allows `to` to be reached right after `from`. A checkpoint without
+    /// transitions ends the sequence, so an initial one has to follow.
+    pub fn transition(mut self, from: &'static str, to: &'static str) -> Self {
+        self.transitions.push((from, to));
+        self
+    }
+
+    pub(crate) fn task(&self) -> &'static str {
+        self.task
+    }
+
+    pub(crate) fn escalation(&self) -> &Escalation {
+        &self.escalation
+    }
+
+    /// Starts supervising from scratch, with the first cycle of the alive
+    /// supervision starting now.
+    pub(crate) fn start(&mut self, now: Timestamp) {
+        for alive in &mut self.alive {
+            alive.count = 0;
+            alive.cycle_start = now;
+        }
+        for deadline in &mut self.deadlines {
+            deadline.started = None;
+        }
+        self.previous = None;
+    }
+
+    pub(crate) fn reached(
+        &mut self,
+        checkpoint: &'static str,
+        now: Timestamp,
+        violations: &mut Vec<Violation>,
+    ) {
+        for alive in &mut self.alive {
+            if alive.checkpoint == checkpoint {
+                alive.count += 1;
+            }
+        }
+        for deadline in &mut self.deadlines {
+            if deadline.to == checkpoint {
+                if let Some(started) = deadline.started.take() {
+                    let elapsed = now - started;
+                    if elapsed < deadline.min || elapsed > deadline.max {
+                        violations.push(Violation::Deadline {
+                            from: deadline.from,
+                            to: deadline.to,
+                            elapsed,
+                        });
+                    }
+                }
+            }
+            if deadline.from == checkpoint {
+                deadline.started = Some(now);
+            }
+        }
+        let logical = self.initial.contains(&checkpoint)
+            || self
+                .transitions
+                .iter()
+                .any(|(from, to)| *from == checkpoint || *to == checkpoint);
+        if logical {
+            let allowed = match self.previous {
+                Some(previous) => self.transitions.contains(&(previous, checkpoint)),
+                None => self.initial.contains(&checkpoint),
+            };
+            if !allowed {
+                violations.push(Violation::Logical {
+                    previous: self.previous,
+                    checkpoint,
+                });
+            }
+            let continues = self.transitions.iter().any(|(from, _)| *from == checkpoint);
+            self.previous = if continues { Some(checkpoint) } else { None };
+        }
+    }
+
+    /// Finds violations that are caused by checkpoints not being reached in
+    /// time.
+    pub(crate) fn check(&mut self, now: Timestamp, violations: &mut Vec<Violation>) {
+        for alive in &mut self.alive {
+            while now >= alive.cycle_start + alive.cycle {
+                if alive.count < alive.min || alive.count > alive.max {
+                    violations.push(Violation::Alive {
+                        checkpoint: alive.checkpoint,
+                        count: alive.count,
+                    });
+                }
+                alive.count = 0;
+                alive.cycle_start += alive.cycle;
+            }
+        }
+        for deadline in &mut self.deadlines {
+            if let Some(started) = deadline.started {
+                if now - started > deadline.max {
+                    deadline.started = None;
+                    violations.push(Violation::Deadline {
+                        from: deadline.from,
+                        to: deadline.to,
+                        elapsed: now - started,
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Erlang-style supervisors for long-running services.
+//!
+//! A [`Supervisor`] starts its children as tasks and restarts them when they
+//! fail, miss their deadline or are otherwise aborted by the executor. If
+//! restarts happen too often, the supervisor gives up, cancels its children
+//! and fails itself, so a supervisor further up the tree can deal with it.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_util::future::poll_fn;
+
+use super::{
+    deadline::Deadline, join::JoinHandle, restart::JobOutput, spawner::Spawner, DelayStrategy, Task,
+};
+use crate::time::{Timer, Timestamp};
+
+/// Which children are restarted when one of them fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartStrategy {
+    /// Only the failed child.
+    OneForOne,
+    /// All children that are still running.
+    OneForAll,
+    /// The failed child and the running children that were added after it.
+    RestForOne,
+}
+
+/// The children of a supervisor were restarted too often.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escalated;
+
+type ChildFuture = Pin<Box<dyn Future<Output = bool>>>;
+
+struct ChildSpec {
+    name: &'static str,
+    relative_deadline: Option<Timestamp>,
+    create: Box<dyn Fn() -> ChildFuture>,
+}
+
+pub struct Supervisor {
+    strategy: RestartStrategy,
+    max_restarts: u32,
+    window: Timestamp,
+    children: Vec<ChildSpec>,
+}
+
+/// A started child. The task finishes early once it's cancelled.
+struct Running {
+    handle: JoinHandle<bool>,
+    cancelled: Rc<Cell<bool>>,
+}
+
+impl Supervisor {
+    /// Escalates if more than `max_restarts` restarts are necessary within
+    /// `window`.
+    pub fn new(strategy: RestartStrategy, max_restarts: u32, window: Timestamp) -> Self {
+        Self {
+            strategy,
+            max_restarts,
+            window,
+            children: Vec::new(),
+        }
+    }
+
+    /// Adds a child. Each time the child is started, a new future is created
+    /// and runs as a task with the given relative deadline. Children returning
+    /// an [`Err`] have failed. Children that finish successfully are not
+    /// restarted.
+    pub fn child<F>(
+        mut self,
+        name: &'static str,
+        relative_deadline: Option<Timestamp>,
+        create: impl Fn() -> F + 'static,
+    ) -> Self
+    where
+        F: Future + 'static,
+        F::Output: JobOutput,
+    {
+        self.children.push(ChildSpec {
+            name,
+            relative_deadline,
+            create: Box::new(move || {
+                let child = create();
+                Box::pin(async move { child.await.is_failure() })
+            }),
+        });
+        self
+    }
+
+    /// Starts all children as tasks through the spawner and supervises them.
+    /// Finishes once all children finished successfully.
+    pub async fn run(self, timer: impl Timer, spawner: Spawner) -> Result<(), Escalated> {
+        let mut running: Vec<Option<Running>> = (0..self.children.len())
+            .map(|index| Some(self.start(index, &spawner)))
+            .collect();
+        let mut restarts: Vec<Timestamp> = Vec::new();
+
+        loop {
+            let exited = poll_fn(|context| next_exit(&mut running, context)).await;
+            let (index, failed) = match exited {
+                Some(exited) => exited,
+                None => return Ok(()),
+            };
+            running[index] = None;
+            if !failed {
+                continue;
+            }
+
+            let now = timer.now();
+            restarts.retain(|time| now - time < self.window);
+            if restarts.len() >= self.max_restarts as usize {
+                for child in running.iter().flatten() {
+                    child.cancelled.set(true);
+                }
+                return Err(Escalated);
+            }
+            restarts.push(now);
+
+            let affected = match self.strategy {
+                RestartStrategy::OneForOne => index..index + 1,
+                RestartStrategy::OneForAll => 0..running.len(),
+                RestartStrategy::RestForOne => index..running.len(),
+            };
+            for other in affected {
+                if let Some(child) = running[other].take() {
+                    child.cancelled.set(true);
+                } else if other != index {
+                    continue;
+                }
+                running[other] = Some(self.start(other, &spawner));
+            }
+        }
+    }
+
+    fn start(&self, index: usize, spawner: &Spawner) -> Running {
+        let spec = &self.children[index];
+        let cancelled = Rc::new(Cell::new(false));
+        let child = Cancellable {
+            cancelled: cancelled.clone(),
+            child: (spec.create)(),
+        };
+        // A missed deadline only ends up in the handle, so it doesn't stop the
+        // executor.
+        let (task, handle) =
+            Task::joinable(Deadline::Infinite, DelayStrategy::SilentlyAbort, child);
+        let task = task.with_name(spec.name);
+        spawner.spawn_task(match spec.relative_deadline {
+            Some(deadline) => task.with_relative_deadline(deadline),
+            None => task,
+        });
+        Running { handle, cancelled }
+    }
+}
+
+/// Waits until a running child exits and returns its index and whether it
+/// failed, or `None` if no children are running.
+fn next_exit(
+    running: &mut [Option<Running>],
+    context: &mut Context,
+) -> Poll<Option<(usize, bool)>> {
+    let mut any_running = false;
+    for (index, child) in running.iter_mut().enumerate() {
+        if let Some(child) = child {
+            any_running = true;
+            if let Poll::Ready(outcome) = Pin::new(&mut child.handle).poll(context) {
+                return Poll::Ready(Some((index, outcome.unwrap_or(true))));
+            }
+        }
+    }
+    if any_running {
+        Poll::Pending
+    } else {
+        Poll::Ready(None)
+    }
+}
+
+struct Cancellable {
+    cancelled: Rc<Cell<bool>>,
+    child: ChildFuture,
+}
+
+impl Future for Cancellable {
+    type Output = bool;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<bool> {
+        if self.cancelled.get() {
+            return Poll::Ready(false);
+        }
+        self.child.as_mut().poll(context)
+    }
+}
@@ -0,0 +1,168 @@
+//! Kicking a hardware watchdog only while the system is healthy.
+//!
+//! The executor kicks its [`Watchdog`] as long as all critical tasks checked
+//! in within their period and no deadline miss was reported as an error since
+//! the fault was last cleared. If a critical task stalls, the kicks stop and
+//! the watchdog eventually resets the system.
+
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::cell::Cell;
+
+use super::local;
+use crate::time::{Timer, Timestamp};
+
+pub trait Watchdog {
+    /// Restarts the watchdog's countdown.
+    fn kick(&mut self);
+}
+
+/// Reports that the current task made progress.
+pub fn check_in() {
+    local::check_in();
+}
+
+/// A watchdog for tests that records its kicks and expires if it isn't
+/// kicked within its timeout.
+#[derive(Clone)]
+pub struct TestWatchdog<T: Timer> {
+    timer: T,
+    timeout: Timestamp,
+    last_kick: Rc<Cell<Timestamp>>,
+    kicks: Rc<Cell<u64>>,
+}
+
+impl<T: Timer> TestWatchdog<T> {
+    pub fn new(timer: T, timeout: Timestamp) -> Self {
+        let now = timer.now();
+        Self {
+            timer,
+            timeout,
+            last_kick: Rc::new(Cell::new(now)),
+            kicks: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn kicks(&self) -> u64 {
+        self.kicks.get()
+    }
+
+    /// Whether the watchdog would have reset the system by now.
+    pub fn expired(&self) -> bool {
+        self.timer.now() - self.last_kick.get() > self.timeout
+    }
+}
+
+impl<T: Timer> Watchdog for TestWatchdog<T> {
+    fn kick(&mut self) {
+        self.last_kick.set(self.timer.now());
+        self.kicks.set(self.kicks.get() + 1);
+    }
+}
+
+/// How often the executor kicks the watchdog and which tasks have to check
+/// in for that.
+pub struct WatchdogConfig {
+    kick_interval: Timestamp,
+    critical: Vec<(&'static str, Timestamp)>,
+}
+
+impl WatchdogConfig {
+    pub fn new(kick_interval: Timestamp) -> Self {
+        Self {
+            kick_interval,
+            critical: Vec::new(),
+        }
+    }
+
+    /// Requires the task with the given name to [`check_in`] at least once
+    /// per period.
+    pub fn critical(mut self, name: &'static str, period: Timestamp) -> Self {
+        self.critical.push((name, period));
+        self
+    }
+}
+
+struct CriticalTask {
+    name: &'static str,
+    period: Timestamp,
+    last_check_in: Timestamp,
+}
+
+pub(crate) struct WatchdogState {
+    watchdog: Box<dyn Watchdog>,
+    kick_interval: Timestamp,
+    last_kick: Option<Timestamp>,
+    critical: Vec<CriticalTask>,
+    /// Whether a deadline miss was reported as an error. Stays set until it's
+    /// cleared explicitly.
+    faulted: bool,
+}
+
+impl WatchdogState {
+    /// Starts watching the critical tasks now.
+    pub fn new(watchdog: Box<dyn Watchdog>, config: WatchdogConfig, now: Timestamp) -> Self {
+        Self {
+            watchdog,
+            kick_interval: config.kick_interval,
+            last_kick: None,
+            critical: config
+                .critical
+                .into_iter()
+                .map(|(name, period)| CriticalTask {
+                    name,
+                    period,
+                    last_check_in: now,
+                })
+                .collect(),
+            faulted: false,
+        }
+    }
+
+    pub fn checked_in(&mut self, name: &'static str, now: Timestamp) {
+        for task in &mut self.critical {
+            if task.name == name {
+                task.last_check_in = now;
+            }
+        }
+    }
+
+    pub fn fault(&mut self) {
+        self.faulted = true;
+    }
+
+    pub fn clear_fault(&mut self) {
+        self.faulted = false;
+    }
+
+    pub fn is_healthy(&self, now: Timestamp) -> bool {
+        !self.faulted
+            && self
+                .critical
+                .iter()
+                .all(|task| now - task.last_check_in <= task.period)
+    }
+
+    /// When the watchdog is kicked next, unless it's faulted.
+    pub fn next_kick(&self) -> Option<Timestamp> {
+        if self.faulted {
+            return None;
+        }
+        Some(match self.last_kick {
+            Some(last_kick) => last_kick + self.kick_interval,
+            None => 0,
+        })
+    }
+
+    /// Kicks the watchdog if the system is healthy and the kick interval
+    /// passed.
+    pub fn service(&mut self, now: Timestamp) {
+        let due = match self.last_kick {
+            Some(last_kick) => now - last_kick >= self.kick_interval,
+            None => true,
+        };
+        if due && self.is_healthy(now) {
+            self.watchdog.kick();
+            self.last_kick = Some(now);
+        }
+    }
+}
@@ -29,7 +29,7 @@ impl IntoDuration for u64 {
 }
 
 #[cfg(feature = "std")]
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct StdTimer;
 
 #[cfg(feature = "std")]
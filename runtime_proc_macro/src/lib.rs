@@ -0,0 +1,196 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Expr, FnArg, Ident, ItemFn, ReturnType, Token,
+};
+
+/// An argument of the form `name = value`.
+struct Arg {
+    name: Ident,
+    value: Expr,
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // Allows `crate` as a name.
+        let name = Ident::parse_any(input)?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Arg { name, value })
+    }
+}
+
+type Args = Punctuated<Arg, Token![,]>;
+
+/// The path under which the generated code finds the runtime. It's given as
+/// `crate = path` and is `::dependability::runtime` by default.
+fn runtime(args: &Args) -> TokenStream2 {
+    match args.iter().find(|arg| arg.name == "crate") {
+        Some(Arg { value, .. }) => quote! { #value },
+        None => quote! { ::dependability::runtime },
+    }
+}
+
+/// Turns an `async fn` into a function that creates a `Task` running it.
+///
+/// Supported arguments are `deadline` (relative to when the task is spawned),
+/// `strategy` (a `DelayStrategy`, `ReturnError` by default), `wcet` (the
+/// worst-case execution time, used as the task's budget) and `name` (the
+/// function's name by default). Crates that only depend on
+/// `dependability-runtime` pass `crate = ::dependability_runtime`.
+#[proc_macro_attribute]
+pub fn task(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr with Args::parse_terminated);
+    let input = parse_macro_input!(item as ItemFn);
+    expand_task(args, input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand_task(args: Args, input: ItemFn) -> syn::Result<TokenStream2> {
+    let rt = runtime(&args);
+    if input.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            &input.sig,
+            "tasks have to be async functions",
+        ));
+    }
+    if let ReturnType::Type(_, ty) = &input.sig.output {
+        return Err(syn::Error::new_spanned(ty, "tasks can't return values"));
+    }
+
+    let ident = &input.sig.ident;
+    let mut name = ident.to_string();
+    let mut strategy = quote! { #rt::task::DelayStrategy::ReturnError };
+    let mut configure = Vec::new();
+    for Arg { name: arg, value } in args {
+        match arg.to_string().as_str() {
+            "crate" => {}
+            "deadline" => configure.push(quote! { .with_relative_deadline(#value) }),
+            "strategy" => strategy = quote! { #value },
+            "wcet" => configure.push(quote! {
+                .with_budget({
+                    let wcet = #value;
+                    #rt::task::criticality::ExecutionBudget::new(wcet, wcet)
+                })
+            }),
+            "name" => match value {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(lit),
+                    ..
+                }) => name = lit.value(),
+                _ => return Err(syn::Error::new_spanned(value, "expected a string literal")),
+            },
+            _ => return Err(syn::Error::new_spanned(arg, "unknown task argument")),
+        }
+    }
+
+    // The outer function takes the same arguments, but without patterns.
+    let mut outer = input.sig.clone();
+    outer.asyncness = None;
+    outer.output = syn::parse_quote! { -> #rt::task::Task };
+    let mut arg_idents = Vec::new();
+    for (index, input) in outer.inputs.iter_mut().enumerate() {
+        match input {
+            FnArg::Typed(typed) => {
+                let arg_ident = format_ident!("arg{}", index);
+                typed.pat = syn::parse_quote! { #arg_ident };
+                arg_idents.push(arg_ident);
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "tasks can't take self"))
+            }
+        }
+    }
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let mut inner = input.clone();
+    inner.attrs.clear();
+    inner.vis = syn::Visibility::Inherited;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #outer {
+            #inner
+
+            #rt::task::Task::new(
+                #rt::task::deadline::Deadline::Infinite,
+                #strategy,
+                #ident(#(#arg_idents),*),
+            )
+            .with_name(#name)
+            #(#configure)*
+        }
+    })
+}
+
+/// Runs the tasks returned by the annotated function on a new executor.
+///
+/// The function returns the tasks to spawn, e.g. as an array, and becomes a
+/// function returning the result of running the executor. The `timer`
+/// argument sets the executor's timer, which is the `StdTimer` by default.
+/// Like for [`macro@task`], `crate` sets the path of the runtime.
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr with Args::parse_terminated);
+    let input = parse_macro_input!(item as ItemFn);
+    expand_main(args, input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand_main(args: Args, input: ItemFn) -> syn::Result<TokenStream2> {
+    let rt = runtime(&args);
+    if !input.sig.inputs.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.sig.inputs,
+            "the entry point can't take arguments",
+        ));
+    }
+    if let Some(asyncness) = &input.sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "the entry point can't be async",
+        ));
+    }
+    let output = match &input.sig.output {
+        ReturnType::Type(..) => &input.sig.output,
+        ReturnType::Default => {
+            return Err(syn::Error::new_spanned(
+                &input.sig,
+                "the entry point has to return the tasks to spawn",
+            ))
+        }
+    };
+
+    let mut timer = quote! { #rt::time::StdTimer };
+    for Arg { name, value } in args {
+        match name.to_string().as_str() {
+            "crate" => {}
+            "timer" => timer = quote! { #value },
+            _ => return Err(syn::Error::new_spanned(name, "unknown argument")),
+        }
+    }
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let ident = &input.sig.ident;
+    let block = &input.block;
+    Ok(quote! {
+        #(#attrs)*
+        #vis fn #ident() -> ::core::result::Result<(), #rt::task::executor::ExecutorError> {
+            fn tasks() #output #block
+
+            let mut executor = #rt::task::executor::Executor::with_timer(#timer);
+            for task in tasks() {
+                executor.spawn(task);
+            }
+            executor.run()
+        }
+    })
+}